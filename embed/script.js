@@ -14,8 +14,71 @@ let url = new URL("/events", window.location.href);
 // http => ws
 // https => wss
 url.protocol = url.protocol.replace("http", "ws");
+// opening the page with ?minimal=true opts in to the smaller event format
+if (new URL(window.location.href).searchParams.get("minimal") === "true") {
+  url.searchParams.set("minimal", "true");
+}
 
 let events = [];
+// opening the page with ?notify=true beeps and shows a desktop notification when the session ends
+const notifyOnExit = new URL(window.location.href).searchParams.get("notify") === "true";
+if (notifyOnExit && "Notification" in window && Notification.permission === "default") {
+  Notification.requestPermission();
+}
+
+function notifySessionEnded(dto) {
+  let audio = new AudioContext();
+  let oscillator = audio.createOscillator();
+  oscillator.connect(audio.destination);
+  oscillator.start();
+  oscillator.stop(audio.currentTime + 0.2);
+  if ("Notification" in window && Notification.permission === "granted") {
+    new Notification("Escape Artist", { body: `Session ended with exit code ${dto.exit_code}` });
+  }
+}
+
+// opening the page with ?positions=true shows where the cursor was in each escape's tooltip
+const showPositions = new URL(window.location.href).searchParams.get("positions") === "true";
+
+function tooltip(dto) {
+  let details = [];
+  if (dto.ts_ms !== undefined) {
+    details.push(`${formatSeconds(dto.ts_ms)} in`);
+  }
+  if (showPositions && dto.cursor_row !== undefined) {
+    details.push(`at (${dto.cursor_row}, ${dto.cursor_col})`);
+  }
+  if (details.length === 0) {
+    return dto.tooltip;
+  }
+  let prefix = details.join(", ");
+  return dto.tooltip ? `${prefix}: ${dto.tooltip}` : prefix;
+}
+
+function formatSeconds(ms) {
+  return `${(ms / 1000).toFixed(3)}s`;
+}
+
+// a pause in the output at least this long gets called out, e.g. to spot a slow redraw
+const GAP_MS = 500;
+
+function Gap(props) {
+  let previous = events[props.index - 1];
+  let dto = props.dto;
+  if (dto.ts_ms === undefined || previous?.ts_ms === undefined || dto.ts_ms - previous.ts_ms < GAP_MS) {
+    return null;
+  }
+  return html`<div class="w-fit text-xs font-sans text-slate-400 mx-1">
+    … ${formatSeconds(dto.ts_ms - previous.ts_ms)} later …
+  </div>`;
+}
+
+// only populated when connecting with ?minimal=true; maps icon keys to SVGs
+let icons = {};
+
+function iconSvg(dto) {
+  return dto.icon_svg || icons[dto.icon];
+}
 
 function showTooltip(event) {
   const tooltip = document.querySelector('#tooltip');
@@ -23,25 +86,50 @@ function showTooltip(event) {
   update(event.target);
 }
 
+// the text-decoration-style for an underline: CSS calls single "solid" and curly "wavy"
+function underlineStyle(underline) {
+  return underline === "curly" ? "wavy" : underline === "single" ? "solid" : underline;
+}
+
+// underlines and strikethrough share text-decoration, so they're combined into one declaration
+function textDecoration(dto) {
+  let lines = [dto.underline ? "underline" : "", dto.strikethrough ? "line-through" : ""].filter((line) => line);
+  if (lines.length === 0) {
+    return "";
+  }
+  return `text-decoration: ${lines.join(" ")} ${dto.underline ? underlineStyle(dto.underline) : ""}`;
+}
+
+// hyperlinks from the child's output are only clickable if they can't run script in this page
+function safeLink(uri) {
+  return /^(https?|ftp|file|mailto):/i.test(uri || "");
+}
+
 function Event(props) {
   let dto = props.dto;
   const shared_classes = "w-fit outline outline-1 rounded-sm px-1 m-1 bg-slate-800"
   switch (dto.type) {
-    case "Print":
-      if (!!dto.color && !!dto.bg_color) {
-        return html`<span style="color: ${dto.color}; background-color: ${dto.bg_color}">${dto.string}</span>`
-      } else if (!!dto.color) {
-        return html`<span style="color: ${dto.color}">${dto.string}</span>`
-      } else if (!!dto.bg_color) {
-        return html`<span style="background-color: ${dto.bg_color}">${dto.string}</span>`
-      } else {
-        return html`<span>${dto.string}</span>`;
-      }
+    case "Print": {
+      let string = dto.show_whitespace ? dto.string.replaceAll(" ", "·") : dto.string;
+      let classes = [dto.blink ? `blink-${dto.blink}` : "", dto.pasted ? "pasted" : "", dto.hyperlink ? "linked" : ""].join(" ");
+      let style = [
+        dto.color ? `color: ${dto.color}` : "",
+        dto.bg_color ? `background-color: ${dto.bg_color}` : "",
+        textDecoration(dto),
+        dto.bold ? "font-weight: bold" : "",
+        dto.dim ? "opacity: 0.6" : "",
+        dto.italic ? "font-style: italic" : "",
+      ].filter((part) => part).join("; ");
+      let span = html`<span class="${classes}" style="${style}" title=${dto.hyperlink}>${string}</span>`;
+      return safeLink(dto.hyperlink)
+        ? html`<a href=${dto.hyperlink} target="_blank" rel="noopener noreferrer">${span}</a>`
+        : span;
+    }
     case "GenericEscape": {
-      let svg = dto.icon_svg ? html`<span class="inline-block align-middle" dangerouslySetInnerHTML=${{ __html: dto.icon_svg}}/>` : html``;
+      let svg = iconSvg(dto) ? html`<span class="inline-block align-middle" dangerouslySetInnerHTML=${{ __html: iconSvg(dto)}}/>` : html``;
       let title = dto.title ? html`<span>${dto.title}</span>` : ``;
       return html`<div
-        data-tooltip=${dto.tooltip}
+        data-tooltip=${tooltip(dto)}
         data-rawbytes=${dto.raw_bytes}
         onmouseenter=${showTooltip}
         onmouseleave=${hideTooltip}
@@ -54,10 +142,10 @@ function Event(props) {
         </div>`;
     }
     case "ColorEscape": {
-      let svg = dto.icon_svg ? html`<span class="inline-block align-middle" dangerouslySetInnerHTML=${{ __html: dto.icon_svg}}/>` : html``;
+      let svg = iconSvg(dto) ? html`<span class="inline-block align-middle" dangerouslySetInnerHTML=${{ __html: iconSvg(dto)}}/>` : html``;
       let title = dto.title ? html`<span>${dto.title}</span>` : ``;
       return html`<div
-        data-tooltip=${dto.tooltip}
+        data-tooltip=${tooltip(dto)}
         data-rawbytes=${dto.raw_bytes}
         onmouseenter=${showTooltip}
         onmouseleave=${hideTooltip}
@@ -69,6 +157,68 @@ function Event(props) {
           ${title}
         </div>`;
     }
+    case "PromptMarker": {
+      let finished = dto.kind === "command_finished";
+      // minimal clients don't get null fields at all
+      let hasExitCode = dto.exit_code !== null && dto.exit_code !== undefined;
+      let label = finished
+        ? (hasExitCode ? `Exit ${dto.exit_code}` : "Finished")
+        : { prompt_start: "Prompt", command_start: "Command", output_start: "Output" }[dto.kind];
+      let description = {
+        prompt_start: "Prompt starts",
+        command_start: "Command starts (end of prompt)",
+        output_start: "Command output starts",
+        command_finished: hasExitCode ? `Command finished with exit code ${dto.exit_code}` : "Command finished",
+      }[dto.kind];
+      let outline = !hasExitCode ? "outline-sky-400" : dto.exit_code === 0 ? "outline-green-500" : "outline-red-500";
+      let marker = html`<div
+        data-tooltip=${description}
+        data-rawbytes=${dto.raw_bytes}
+        onmouseenter=${showTooltip}
+        onmouseleave=${hideTooltip}
+        onfocus=${showTooltip}
+        onblur=${hideTooltip}
+        class="inline-block font-sans text-xs ${outline} ${shared_classes}"
+        >
+          ${label}
+        </div>`;
+      // each prompt starts a new section, so a command and its output stay together
+      return dto.kind === "prompt_start" ? html`<div class="border-t border-slate-600 mt-2">${marker}</div>` : marker;
+    }
+    case "Sgr": {
+      return html`<div
+        data-tooltip=${dto.attributes.join("; ")}
+        data-rawbytes=${dto.raw_bytes}
+        onmouseenter=${showTooltip}
+        onmouseleave=${hideTooltip}
+        onfocus=${showTooltip}
+        onblur=${hideTooltip}
+        class="inline-block outline-slate-400 ${shared_classes}"
+        >
+          SGR
+        </div>`;
+    }
+    case "Diff": {
+      // what's only in the original recording is struck out in red, what's only in the new one is green
+      let classes = dto.side === "removed" ? "bg-red-900 line-through" : "bg-green-900";
+      return html`<span class="${classes}"><${Event} dto=${dto.event} /></span>`;
+    }
+    case "Image": {
+      return html`<div class="inline-block align-top">
+        <div
+          data-tooltip=${tooltip(dto)}
+          data-rawbytes=${dto.raw_bytes}
+          onmouseenter=${showTooltip}
+          onmouseleave=${hideTooltip}
+          onfocus=${showTooltip}
+          onblur=${hideTooltip}
+          class="inline-block outline-slate-400 ${shared_classes}"
+          >
+            ${dto.title}
+        </div>
+        <img src=${dto.data_url} alt=${dto.tooltip} class="block m-1 max-w-full" style="image-rendering: pixelated" />
+      </div>`;
+    }
     case "InvisibleLineBreak": {
       return html`<div/>`;
     }
@@ -78,6 +228,32 @@ function Event(props) {
         ${dto.title}
       </span>`;
     }
+    case "SessionEnded": {
+      let exit_code = dto.exit_code === null ? "unknown" : dto.exit_code;
+      return html`<div class="${shared_classes} font-sans ${dto.exit_code === 0 ? "outline-green-500" : "outline-red-500"}">
+        Session ended (exit code ${exit_code}, ${dto.sequence_count} escape sequences)
+      </div>`;
+    }
+    case "Truncated": {
+      return html`<div class="${shared_classes} font-sans outline-slate-400">
+        Stopped decoding after ${dto.limit} escape sequences (--head)
+      </div>`;
+    }
+    case "EventsDropped": {
+      return html`<div class="${shared_classes} font-sans outline-slate-400">
+        ${dto.count} earlier events were dropped (--max-events)
+      </div>`;
+    }
+    case "Skipped": {
+      return html`<div class="${shared_classes} font-sans outline-red-500">
+        … ${dto.count} events skipped …
+      </div>`;
+    }
+    case "Annotation": {
+      return html`<div class="${shared_classes} font-sans outline-yellow-400">
+        Note on event ${dto.target_id}: ${dto.note}
+      </div>`;
+    }
     case "Disconnected": {
       return html`<div class="${shared_classes} outline-red-500">
         Disconnected
@@ -88,24 +264,151 @@ function Event(props) {
   }
 }
 
-let ws = new WebSocket(url.href);
-ws.onmessage = async (ev) => {
+// the seq of the newest event from the server, so a reconnect can pick up where it left off
+let lastSeq = null;
+let ws = null;
+
+function connect() {
+  let connectUrl = new URL(url);
+  if (lastSeq !== null && lastSeq > 0) {
+    // the newest event may be a print that has grown since, so it's dropped and sent again whole
+    events = events.filter((event) => event.seq !== lastSeq);
+    connectUrl.searchParams.set("since", lastSeq - 1);
+  }
+  ws = new WebSocket(connectUrl.href);
+  ws.onopen = (_) => {
+    events = events.filter((event) => event.type !== "Disconnected");
+    renderAndScroll();
+  };
+  ws.onmessage = onMessage;
+  ws.onclose = (_) => {
+    if (events.at(-1)?.type !== "Disconnected") {
+      events.push({ type: "Disconnected" });
+      renderAndScroll();
+    }
+    // keep trying, e.g. after the laptop wakes up; once the server is gone this just fails quietly
+    setTimeout(connect, 2000);
+  };
+}
+
+async function onMessage(ev) {
   let deserialized = JSON.parse(ev.data);
   for (const event of deserialized) {
+    if (event.type === "IconMap") {
+      icons = event.icons;
+      continue;
+    }
+    // the server is about to resend events, e.g. after seeking to a point in time
+    if (event.type === "Clear") {
+      events = [];
+      lastSeq = null;
+      continue;
+    }
+    if (event.type === "SessionEnded" && notifyOnExit) {
+      notifySessionEnded(event);
+    }
+    if (event.seq !== undefined) {
+      lastSeq = event.seq;
+    }
     events.push(event);
     // console.log(event);
   }
+  if (screen !== null) {
+    refreshScreen();
+  }
   renderAndScroll();
-};
+}
 
-ws.onclose = (_) => {
-  events.push({ type: "Disconnected" });
-  renderAndScroll();
-};
+connect();
+
+function clearEvents() {
+  // the server tells every client (including this one) to clear once it's done
+  fetch("/clear", { method: "POST" });
+}
+
+// what the terminal would be showing, from /screen, or null when the rendered view is closed
+let screen = null;
+let screenRequest = null;
+
+function toggleScreen() {
+  if (screen === null) {
+    refreshScreen();
+  } else {
+    screen = null;
+    renderAndScroll();
+  }
+}
+
+// at most one request in flight, so a burst of events doesn't turn into a burst of fetches
+function refreshScreen() {
+  if (screenRequest !== null) {
+    return;
+  }
+  screenRequest = fetch("/screen")
+    .then((response) => response.json())
+    .then((snapshot) => {
+      screen = snapshot;
+      renderAndScroll();
+    })
+    .finally(() => {
+      screenRequest = null;
+    });
+}
+
+function ScreenSpan({ span }) {
+  let style = {
+    color: span.color,
+    backgroundColor: span.bg_color,
+    fontWeight: span.bold ? "bold" : undefined,
+    fontStyle: span.italic ? "italic" : undefined,
+    opacity: span.dim ? 0.6 : undefined,
+    textDecoration: [span.underline && "underline", span.strikethrough && "line-through"]
+      .filter(Boolean)
+      .join(" ") || undefined,
+  };
+  return html`<span style=${style}>${span.text}</span>`;
+}
+
+// the cursor's cell is split out of its span so it can be drawn as a block
+function ScreenRow({ spans, cursorCol }) {
+  let col = 0;
+  let parts = [];
+  for (const span of spans) {
+    let chars = Array.from(span.text);
+    if (cursorCol !== null && cursorCol >= col && cursorCol < col + chars.length) {
+      let at = cursorCol - col;
+      parts.push(html`<${ScreenSpan} span=${{ ...span, text: chars.slice(0, at).join("") }} />`);
+      parts.push(html`<span class="bg-slate-200 text-slate-900">${chars[at]}</span>`);
+      parts.push(html`<${ScreenSpan} span=${{ ...span, text: chars.slice(at + 1).join("") }} />`);
+    } else {
+      parts.push(html`<${ScreenSpan} span=${span} />`);
+    }
+    col += chars.length;
+  }
+  return html`<div class="whitespace-pre">${parts}</div>`;
+}
+
+function ScreenView({ screen }) {
+  return html`
+    <div class="fixed top-9 right-2 z-10 max-w-[90vw] max-h-[80vh] overflow-auto p-2 rounded-sm bg-slate-950 border border-slate-600">
+      <div class="font-sans text-xs mb-1 text-slate-400">
+        ${screen.rows}×${screen.cols}, cursor at row ${screen.cursor_row + 1}, column ${screen.cursor_col + 1}
+      </div>
+      ${screen.lines.map((spans, row) => html`<${ScreenRow} spans=${spans} cursorCol=${row === screen.cursor_row ? screen.cursor_col : null} />`)}
+    </div>
+  `;
+}
 
 function renderAndScroll() {
   render(
     html`
+    <button class="fixed top-2 right-2 font-sans text-sm px-2 rounded-sm bg-slate-700 hover:bg-slate-600" onclick=${clearEvents}>
+      Clear
+    </button>
+    <button class="fixed top-2 right-20 font-sans text-sm px-2 rounded-sm bg-slate-700 hover:bg-slate-600" onclick=${toggleScreen}>
+      ${screen === null ? "Screen" : "Hide screen"}
+    </button>
+    ${screen !== null && html`<${ScreenView} screen=${screen} />`}
     <div id="tooltip" class="hidden bg-slate-800 p-2 rounded-sm w-max absolute top-0 left-0" role="tooltip">
       <div class="flex flex-col items-center">
         <div id="description" class="font-sans font-semibold text-sm mb-1"/>
@@ -113,7 +416,7 @@ function renderAndScroll() {
       </div>
       <div id="arrow" class="absolute bg-slate-800 w-2 h-2 rotate-45"></div>
     </div>
-    ${events.map((event) => html`<${Event} dto="${event}" />`)}
+    ${events.map((event, index) => html`<${Gap} dto="${event}" index="${index}" /><${Event} dto="${event}" />`)}
     `,
     document.body
   );
@@ -0,0 +1,27 @@
+//! Arbitrary output shouldn't be able to crash the decoder or the virtual screen, and whatever they
+//! produce has to reach the browser as valid JSON. Run with `cargo +nightly fuzz run decode`
+
+#![no_main]
+
+use escape_artist::{parse_bytes, screen::VirtualScreen};
+use libfuzzer_sys::fuzz_target;
+use termwiz::escape::parser::Parser;
+
+fuzz_target!(|data: &[u8]| {
+    for dto in escape_artist::decode_stream(data) {
+        for minimal in [false, true] {
+            let json = escape_artist::serialize_dtos(&[dto.clone()], minimal);
+            serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        }
+    }
+
+    let mut screen = VirtualScreen::default();
+    parse_bytes(&mut Parser::new(), &mut vec![], data, |actions, _| {
+        for action in &actions {
+            screen.apply(action);
+        }
+    });
+    let json = serde_json::to_string(&screen.snapshot()).unwrap();
+    serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    screen.text();
+});
@@ -0,0 +1,161 @@
+//! Reading and writing asciinema v2 recordings (`.cast` files): a JSON header line, then a
+//! `[time, code, data]` JSON array per event. Only the output events (code "o") matter here
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Cursor, Read, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// Whether `file` is an asciinema recording, going by its extension or else the first line of `head`, the
+/// start of its contents
+pub fn is_cast(file: &str, head: &[u8]) -> bool {
+    if Path::new(file).extension().is_some_and(|ext| ext == "cast") {
+        return true;
+    }
+    let first_line = head.split(|b| *b == b'\n').next().unwrap_or_default();
+    first_line.starts_with(b"{") && serde_json::from_slice::<Header>(first_line).is_ok()
+}
+
+/// Each time the recorded program wrote something: when, in seconds since the recording started, and
+/// what. Any other kinds of event (like input) are left out
+pub fn parse_output_events(file: &str, contents: &[u8]) -> Result<Vec<(f64, Vec<u8>)>> {
+    let contents = std::str::from_utf8(contents).with_context(|| {
+        format!("{file} isn't valid UTF-8, so it can't be an asciinema recording")
+    })?;
+    let mut lines = contents.lines().enumerate();
+    let Some((_, header)) = lines.next() else {
+        bail!("{file} is empty");
+    };
+    let header: Header = serde_json::from_str(header)
+        .with_context(|| format!("line 1 of {file} isn't an asciinema header"))?;
+    if header.version != 2 {
+        bail!(
+            "{file} is an asciinema v{} recording, only v2 is supported",
+            header.version
+        );
+    }
+
+    let mut output = vec![];
+    for (i, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, code, data): (f64, String, String) =
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "line {} of {file} isn't an asciinema event like [time, code, data]",
+                    i + 1
+                )
+            })?;
+        if code == "o" {
+            output.push((time, data.into_bytes()));
+        }
+    }
+    Ok(output)
+}
+
+/// Writes output to an asciinema v2 recording as it's read. Every event is flushed straight away, so
+/// the recording is still usable if escape-artist doesn't exit cleanly
+pub struct CastWriter {
+    file: BufWriter<File>,
+    /// When the first output was written; event times are relative to it
+    start: Option<Instant>,
+    /// The start of a UTF-8 character that was split across reads
+    partial: Vec<u8>,
+}
+
+impl CastWriter {
+    pub fn create(path: &str, (cols, rows): (u16, u16)) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let header = serde_json::json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": timestamp,
+        });
+        writeln!(file, "{header}")?;
+        file.flush()?;
+        Ok(Self {
+            file,
+            start: None,
+            partial: vec![],
+        })
+    }
+
+    pub fn write_output(&mut self, bytes: &[u8]) -> Result<()> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        self.partial.extend_from_slice(bytes);
+        // events are JSON strings, so a character split across reads waits for the rest of it
+        let complete = match std::str::from_utf8(&self.partial) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => self.partial.len(),
+        };
+        let rest = self.partial.split_off(complete);
+        let data = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial = rest;
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // asciinema itself writes times to the microsecond
+        let time = (start.elapsed().as_secs_f64() * 1e6).round() / 1e6;
+        writeln!(self.file, "{}", serde_json::json!([time, "o", data]))?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads an asciinema recording's output at the pace it was recorded, sped up by `speed`, so it can be
+/// watched as it unfolds
+pub struct TimedReader {
+    events: std::vec::IntoIter<(f64, Vec<u8>)>,
+    speed: f64,
+    /// When the first read happened; event times are relative to it
+    start: Option<Instant>,
+    /// What's left of the latest event, if it didn't fit in one read
+    pending: Cursor<Vec<u8>>,
+}
+
+impl TimedReader {
+    pub fn new(events: Vec<(f64, Vec<u8>)>, speed: f64) -> Self {
+        Self {
+            events: events.into_iter(),
+            speed,
+            start: None,
+            pending: Cursor::default(),
+        }
+    }
+}
+
+impl Read for TimedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.pending.read(buf)?;
+            if n > 0 {
+                return Ok(n);
+            }
+            let Some((time, data)) = self.events.next() else {
+                return Ok(0);
+            };
+            let start = *self.start.get_or_insert_with(Instant::now);
+            // a nonsense time (like a negative one) just means no waiting
+            let offset = Duration::try_from_secs_f64(time / self.speed).unwrap_or_default();
+            thread::sleep((start + offset).saturating_duration_since(Instant::now()));
+            self.pending = Cursor::new(data);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Sequence alignment for `escape-artist diff`.
+
+/// One step in turning sequence A into sequence B
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// `a[i]` and `b[j]` are the same
+    Equal(usize, usize),
+    /// `a[i]` is missing from B
+    Removed(usize),
+    /// `b[j]` is missing from A
+    Added(usize),
+}
+
+/// Past this many cells the LCS table gets too big, and the differing middle is reported as a wholesale replacement
+const MAX_TABLE_CELLS: usize = 25_000_000;
+
+/// Align two sequences using their longest common subsequence
+pub fn align<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    // common prefixes and suffixes are cheap to find and usually most of the input
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Equal(i, i)).collect();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    if a_mid.len().saturating_mul(b_mid.len()) > MAX_TABLE_CELLS {
+        edits.extend((0..a_mid.len()).map(|i| Edit::Removed(prefix + i)));
+        edits.extend((0..b_mid.len()).map(|j| Edit::Added(prefix + j)));
+    } else {
+        edits.extend(lcs_edits(a_mid, b_mid).into_iter().map(|edit| match edit {
+            Edit::Equal(i, j) => Edit::Equal(prefix + i, prefix + j),
+            Edit::Removed(i) => Edit::Removed(prefix + i),
+            Edit::Added(j) => Edit::Added(prefix + j),
+        }));
+    }
+
+    let a_suffix_start = a.len() - suffix;
+    let b_suffix_start = b.len() - suffix;
+    edits.extend((0..suffix).map(|k| Edit::Equal(a_suffix_start + k, b_suffix_start + k)));
+    edits
+}
+
+fn lcs_edits<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let (n, m) = (a.len(), b.len());
+    // lengths[i][j] is the LCS length of a[i..] and b[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[at(i, j)] = if a[i] == b[j] {
+                lengths[at(i + 1, j + 1)] + 1
+            } else {
+                lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+            };
+        }
+    }
+
+    let mut edits = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            edits.push(Edit::Equal(i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[at(i + 1, j)] >= lengths[at(i, j + 1)] {
+            edits.push(Edit::Removed(i));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(j));
+            j += 1;
+        }
+    }
+    edits.extend((i..n).map(Edit::Removed));
+    edits.extend((j..m).map(Edit::Added));
+    edits
+}
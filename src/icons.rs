@@ -0,0 +1,105 @@
+//! The icons shown for escape sequences, keyed by a short stable name.
+//! Clients in minimal mode get the whole map once and then only receive the keys.
+//! `--icons <FILE>` swaps out any of the defaults without recompiling.
+
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+
+const DEFAULTS: &[(&str, &str)] = &[
+    ("answerback", iconify::svg!("mdi:message-reply-text")),
+    ("backspace", iconify::svg!("mdi:backspace")),
+    ("bell", iconify::svg!("mdi:bell")),
+    ("charset", iconify::svg!("mdi:alphabetical-variant")),
+    ("color-reset", iconify::svg!("mdi:invert-colors-off")),
+    ("cursor", iconify::svg!("ph:cursor-text-fill")),
+    ("erase", iconify::svg!("mdi:eraser")),
+    ("focus", iconify::svg!("mdi:image-filter-center-focus")),
+    ("hyperlink", iconify::svg!("mdi:link")),
+    ("hyperlink-off", iconify::svg!("mdi:link-off")),
+    ("image", iconify::svg!("mdi:image")),
+    ("keyboard", iconify::svg!("mdi:keyboard")),
+    ("mode-off", iconify::svg!("mdi:toggle-switch-off-outline")),
+    ("mode-on", iconify::svg!("mdi:toggle-switch")),
+    ("mouse", iconify::svg!("mdi:mouse")),
+    ("printer", iconify::svg!("mdi:printer")),
+    ("paste", iconify::svg!("mdi:content-paste")),
+    ("pause", iconify::svg!("mdi:pause")),
+    ("play", iconify::svg!("mdi:play")),
+    ("progress", iconify::svg!("mdi:progress-clock")),
+    ("reset", iconify::svg!("carbon:reset")),
+    ("restore-cursor", iconify::svg!("mdi:file-restore")),
+    ("save-cursor", iconify::svg!("mdi:content-save")),
+    ("shield", iconify::svg!("mdi:shield-outline")),
+    ("tab", iconify::svg!("mdi:keyboard-tab")),
+    ("title", iconify::svg!("mdi:format-title")),
+    ("unknown", iconify::svg!("mdi:question-mark-box")),
+    ("window", iconify::svg!("mdi:dock-window")),
+];
+
+static OVERRIDES: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+fn default(key: &str) -> Option<&'static str> {
+    DEFAULTS
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, svg)| *svg)
+}
+
+/// Load a JSON object mapping icon keys to either an inline SVG or the key of one of the default icons
+pub fn load_overrides(path: &str) -> Result<()> {
+    let overrides: BTreeMap<String, String> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut resolved = BTreeMap::new();
+    for (key, value) in overrides {
+        if default(&key).is_none() {
+            warn!("{path} overrides icon {key:?}, which no escape sequence uses");
+        }
+        let svg = if value.trim_start().starts_with("<svg") {
+            value
+        } else if let Some(svg) = default(&value) {
+            svg.to_string()
+        } else {
+            bail!("the icon for {key:?} in {path} should be an inline <svg> or the name of a built-in icon, not {value:?}");
+        };
+        resolved.insert(key, svg);
+    }
+    info!("loaded {} icon overrides from {path}", resolved.len());
+    if OVERRIDES.set(resolved).is_err() {
+        bail!("icon overrides were already loaded");
+    }
+    Ok(())
+}
+
+pub fn icon(key: &str) -> Option<String> {
+    OVERRIDES
+        .get()
+        .and_then(|overrides| overrides.get(key))
+        .map(String::as_str)
+        .or_else(|| default(key))
+        .map(Into::into)
+}
+
+pub fn icon_key(svg: &str) -> Option<&'static str> {
+    OVERRIDES
+        .get()
+        .and_then(|overrides| overrides.iter().find(|(_, s)| *s == svg))
+        .map(|(k, _)| k.as_str())
+        .or_else(|| DEFAULTS.iter().find(|(_, s)| *s == svg).map(|(k, _)| *k))
+}
+
+/// Every icon key and the SVG it currently maps to
+pub fn all() -> BTreeMap<&'static str, String> {
+    DEFAULTS
+        .iter()
+        .map(|(key, _)| *key)
+        .chain(
+            OVERRIDES
+                .get()
+                .into_iter()
+                .flat_map(|o| o.keys().map(String::as_str)),
+        )
+        .filter_map(|key| Some((key, icon(key)?)))
+        .collect()
+}
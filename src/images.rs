@@ -0,0 +1,379 @@
+//! Turning Sixel and Kitty graphics payloads into `data:` URLs the browser can show inline.
+//! Pixel data is wrapped in an uncompressed PNG, which keeps this free of an image crate
+
+use termwiz::escape::{
+    apc::{KittyImageCompression, KittyImageData, KittyImageFormat, KittyImageTransmit},
+    Sixel, SixelData,
+};
+
+/// Images whose data URL would be bigger than this are shown as an icon instead, so one picture
+/// can't swamp the websocket or the page
+const MAX_DATA_URL_LEN: usize = 1 << 20;
+
+/// A decoded image, ready to put in an `<img>`
+pub struct InlineImage {
+    pub width: u32,
+    pub height: u32,
+    pub data_url: String,
+}
+
+/// Render a Sixel image, or `None` if it's empty or too big to inline
+pub fn sixel_image(sixel: &Sixel) -> Option<InlineImage> {
+    let (width, height) = sixel_dimensions(&sixel.data);
+    check_size(width, height)?;
+
+    let mut palette = DEFAULT_SIXEL_PALETTE;
+    let background = if sixel.background_is_transparent {
+        [0, 0, 0, 0]
+    } else {
+        rgba(palette[0])
+    };
+    let mut pixels = background.repeat((width * height) as usize);
+    let (mut x, mut y, mut color) = (0u32, 0u32, 0u16);
+    let paint = |x: u32, y: u32, bits: u8, color: [u8; 4], pixels: &mut Vec<u8>| {
+        for bit in 0..6 {
+            if bits & (1 << bit) != 0 {
+                let offset = (((y + bit) * width + x) * 4) as usize;
+                pixels[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    };
+
+    for data in &sixel.data {
+        let current = rgba(palette[color as usize % palette.len()]);
+        match data {
+            SixelData::Data(bits) => {
+                paint(x, y, *bits, current, &mut pixels);
+                x += 1;
+            }
+            SixelData::Repeat { repeat_count, data } => {
+                for _ in 0..*repeat_count {
+                    paint(x, y, *data, current, &mut pixels);
+                    x += 1;
+                }
+            }
+            SixelData::DefineColorMapRGB { color_number, rgb } => {
+                let (r, g, b) = rgb.to_tuple_rgb8();
+                palette[*color_number as usize % palette.len()] = [r, g, b];
+            }
+            SixelData::DefineColorMapHSL {
+                color_number,
+                hue_angle,
+                saturation,
+                lightness,
+            } => {
+                palette[*color_number as usize % palette.len()] =
+                    hls_to_rgb(*hue_angle, *lightness, *saturation);
+            }
+            SixelData::SelectColorMapEntry(number) => color = *number,
+            SixelData::CarriageReturn => x = 0,
+            SixelData::NewLine => {
+                x = 0;
+                y += 6;
+            }
+        }
+    }
+
+    inline_png(width, height, &pixels)
+}
+
+/// Render the payload of a Kitty transmit command, or `None` if there's nothing we can show. Only
+/// uncompressed data sent directly in a single chunk can be decoded; files, shared memory, zlib and
+/// chunked transfers aren't available to us
+pub fn kitty_image(transmit: &KittyImageTransmit) -> Option<InlineImage> {
+    if transmit.more_data_follows || transmit.compression != KittyImageCompression::None {
+        return None;
+    }
+    let bytes = match &transmit.data {
+        KittyImageData::Direct(payload) => base64_decode(payload)?,
+        KittyImageData::DirectBin(bytes) => bytes.clone(),
+        _ => return None,
+    };
+
+    match transmit.format {
+        Some(KittyImageFormat::Png) => {
+            // the width and height live in the IHDR chunk, right after the 8 byte signature
+            if bytes.len() < 24 || !bytes.starts_with(PNG_SIGNATURE) {
+                return None;
+            }
+            let width = u32::from_be_bytes(bytes[16..20].try_into().ok()?);
+            let height = u32::from_be_bytes(bytes[20..24].try_into().ok()?);
+            data_url("image/png", &bytes).map(|data_url| InlineImage {
+                width,
+                height,
+                data_url,
+            })
+        }
+        // RGBA is the default when the format isn't given
+        ref format => {
+            let channels = if matches!(format, Some(KittyImageFormat::Rgb)) {
+                3
+            } else {
+                4
+            };
+            let (width, height) = (transmit.width?, transmit.height?);
+            check_size(width, height)?;
+            let len = (width * height) as usize * channels;
+            if bytes.len() < len {
+                return None;
+            }
+            let pixels: Vec<u8> = if channels == 4 {
+                bytes[..len].to_vec()
+            } else {
+                bytes[..len]
+                    .chunks_exact(3)
+                    .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect()
+            };
+            inline_png(width, height, &pixels)
+        }
+    }
+}
+
+/// The width and height covered by the sixels, which can be less than the raster attributes declare
+fn sixel_dimensions(data: &[SixelData]) -> (u32, u32) {
+    let (mut x, mut y, mut width, mut height) = (0u32, 0u32, 0u32, 0u32);
+    for data in data {
+        match data {
+            SixelData::Data(_) => x += 1,
+            SixelData::Repeat { repeat_count, .. } => x += repeat_count,
+            SixelData::CarriageReturn => x = 0,
+            SixelData::NewLine => {
+                x = 0;
+                y += 6;
+            }
+            _ => continue,
+        }
+        width = width.max(x);
+        if x > 0 {
+            height = height.max(y + 6);
+        }
+    }
+    (width, height)
+}
+
+/// `None` if the image is empty, or so big its data URL would blow past `MAX_DATA_URL_LEN`
+fn check_size(width: u32, height: u32) -> Option<()> {
+    let pixels = (width as usize).checked_mul(height as usize)?;
+    (pixels > 0 && pixels.saturating_mul(4) <= MAX_DATA_URL_LEN).then_some(())
+}
+
+fn rgba([r, g, b]: [u8; 3]) -> [u8; 4] {
+    [r, g, b, 255]
+}
+
+/// Sixel colors use HLS with blue at 0°, which is 240° in the usual HSL wheel. Lightness and
+/// saturation are percentages
+fn hls_to_rgb(hue: u16, lightness: u8, saturation: u8) -> [u8; 3] {
+    let hue = ((hue as f32) + 240.) % 360.;
+    let lightness = lightness.min(100) as f32 / 100.;
+    let saturation = saturation.min(100) as f32 / 100.;
+    let chroma = (1. - (2. * lightness - 1.).abs()) * saturation;
+    let x = chroma * (1. - ((hue / 60.) % 2. - 1.).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    let m = lightness - chroma / 2.;
+    let channel = |c: f32| ((c + m) * 255.).round() as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+/// The VT340's startup colors, which images use until they define their own
+const DEFAULT_SIXEL_PALETTE: [[u8; 3]; 256] = {
+    let vt340: [[u8; 3]; 16] = [
+        [0, 0, 0],
+        [51, 51, 204],
+        [204, 36, 36],
+        [51, 204, 51],
+        [204, 51, 204],
+        [51, 204, 204],
+        [204, 204, 51],
+        [120, 120, 120],
+        [69, 69, 69],
+        [87, 87, 153],
+        [153, 69, 69],
+        [87, 153, 87],
+        [153, 87, 153],
+        [87, 153, 153],
+        [153, 153, 87],
+        [204, 204, 204],
+    ];
+    let mut palette = [[0; 3]; 256];
+    let mut i = 0;
+    while i < 256 {
+        palette[i] = vt340[i % 16];
+        i += 1;
+    }
+    palette
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A `data:` URL for RGBA pixels, stored in a PNG without compression
+fn inline_png(width: u32, height: u32, pixels: &[u8]) -> Option<InlineImage> {
+    // each row starts with a filter type byte, 0 for none
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks_exact(width as usize * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // a zlib stream made of stored deflate blocks, which can each hold up to 65535 bytes
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, standard filtering, no interlacing
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    png_chunk(&mut png, b"IHDR", &ihdr);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+
+    data_url("image/png", &png).map(|data_url| InlineImage {
+        width,
+        height,
+        data_url,
+    })
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn data_url(mime: &str, bytes: &[u8]) -> Option<String> {
+    let url = format!("data:{mime};base64,{}", base64_encode(bytes));
+    (url.len() <= MAX_DATA_URL_LEN).then_some(url)
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Kitty payloads are standard base64; padding is optional
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in encoded
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = BASE64_ALPHABET.iter().position(|a| *a == c)? as u32;
+        n = (n << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips() {
+        for bytes in [&b""[..], b"a", b"ab", b"abc", b"\x00\xff\x10\x80"] {
+            assert_eq!(base64_decode(&base64_encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_decode("YWI").unwrap(), b"ab");
+        assert!(base64_decode("not base64!").is_none());
+    }
+
+    #[test]
+    fn png_checksums() {
+        // every PNG ends with this IEND chunk checksum
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn pixels_become_a_png_data_url() {
+        let image = inline_png(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        let png = base64_decode(
+            image
+                .data_url
+                .strip_prefix("data:image/png;base64,")
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(png.starts_with(PNG_SIGNATURE));
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+    }
+
+    #[test]
+    fn huge_images_are_not_inlined() {
+        assert!(check_size(100, 100).is_some());
+        assert!(check_size(0, 100).is_none());
+        assert!(check_size(10_000, 10_000).is_none());
+    }
+}
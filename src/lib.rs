@@ -0,0 +1,3855 @@
+//! The decoding half of escape-artist: turning a terminal program's output into `VteEventDto`s that describe
+//! each escape sequence. The binary adds the pty, the web server and the subcommands on top of this
+
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read},
+    mem::take,
+};
+
+pub mod icons;
+mod images;
+pub mod screen;
+
+use ansi_colours::rgb_from_ansi256;
+use icons::{icon, icon_key};
+use log::warn;
+use serde::Serialize;
+use termwiz::{
+    cell::{Blink, Intensity, Underline},
+    color::ColorSpec,
+    escape::{
+        apc::{
+            KittyImage, KittyImageCompression, KittyImageData, KittyImageDelete, KittyImageFormat,
+            KittyImagePlacement, KittyImageTransmit,
+        },
+        csi::{
+            CsiParam, Cursor, CursorStyle, CursorTabulationControl, DecPrivateMode,
+            DecPrivateModeCode, Device, Edit, EraseInDisplay, EraseInLine, Mode, MouseButton,
+            MouseReport, Sgr, TabulationClear, TerminalMode, Unspecified, Window,
+            XtermKeyModifierResource,
+        },
+        osc::{ColorOrQuery, DynamicColorNumber, FinalTermSemanticPrompt},
+        parser::Parser,
+        Action, ControlCode, DeviceControlMode, EnterDeviceControlMode, Esc, EscCode,
+        OperatingSystemCommand, CSI,
+    },
+    input::Modifiers,
+};
+
+/// Whether termwiz couldn't make sense of a sequence and we didn't pick it apart ourselves either
+pub fn is_undecoded(action: &Action, dto: &VteEventDto) -> bool {
+    // our own decoders fall back to these titles when they don't recognize an unspecified sequence
+    let has_fallback_title = |fallback: &str| matches!(dto, VteEventDto::GenericEscape { title: Some(title), .. } if title == fallback);
+    match action {
+        Action::Esc(Esc::Unspecified { .. }) => {
+            matches!(dto, VteEventDto::GenericEscape { icon_svg, .. } if *icon_svg == icon("unknown"))
+        }
+        Action::CSI(CSI::Unspecified(_)) => has_fallback_title("CSI"),
+        Action::DeviceControl(_) => has_fallback_title("DCS"),
+        Action::OperatingSystemCommand(osc) => {
+            matches!(**osc, OperatingSystemCommand::Unspecified(_)) && has_fallback_title("OSC")
+        }
+        _ => false,
+    }
+}
+
+/// A broad description of what kind of action something is, e.g. "CSI" or "Print"
+pub fn action_category(action: &Action) -> &'static str {
+    if reset_kind(action).is_some() {
+        return "Reset";
+    }
+    match action {
+        Action::Print(_) | Action::PrintString(_) => "Print",
+        Action::Control(_) => "Control",
+        Action::DeviceControl(_) => "DCS",
+        Action::OperatingSystemCommand(_) => "OSC",
+        Action::CSI(_) => "CSI",
+        Action::Esc(_) => "ESC",
+        Action::Sixel(_) => "Sixel",
+        Action::XtGetTcap(_) => "XTGETTCAP",
+        Action::KittyImage(_) => "Kitty",
+    }
+}
+
+/// Sequences that put some part of the terminal's state back to how it started. Programs that send a
+/// lot of these tend to flicker
+pub fn reset_kind(action: &Action) -> Option<&'static str> {
+    Some(match action {
+        Action::Esc(Esc::Code(EscCode::FullReset)) => "full reset (RIS)",
+        Action::CSI(CSI::Device(device)) if matches!(**device, Device::SoftReset) => {
+            "soft reset (DECSTR)"
+        }
+        Action::CSI(CSI::Sgr(Sgr::Reset)) => "SGR reset",
+        Action::CSI(CSI::Edit(Edit::EraseInDisplay(EraseInDisplay::EraseDisplay))) => {
+            "erase display"
+        }
+        Action::Esc(Esc::Code(EscCode::DecRestoreCursorPosition))
+        | Action::CSI(CSI::Cursor(Cursor::RestoreCursor)) => "restore cursor",
+        Action::OperatingSystemCommand(osc)
+            if matches!(**osc, OperatingSystemCommand::ResetDynamicColor(_)) =>
+        {
+            "reset dynamic color"
+        }
+        Action::OperatingSystemCommand(osc)
+            if matches!(**osc, OperatingSystemCommand::ResetColors(_)) =>
+        {
+            "reset palette"
+        }
+        _ => return None,
+    })
+}
+
+/// The actions parsed from one byte sequence, along with those bytes
+pub type Sequence = (Vec<Action>, Vec<u8>);
+
+/// Feed bytes to the parser one at a time so that actions can be paired with the raw bytes that produced them.
+/// 1 byte sequence can represent multiple actions, so they're passed along together.
+/// `pending_bytes` holds the bytes of an incomplete sequence between calls. That includes a multibyte UTF-8
+/// character split across two reads: the parser buffers it and only emits a `Print` once the last byte
+/// arrives, so the character and all of its bytes end up in the same sequence. Keep `parser` and
+/// `pending_bytes` alive for the whole stream, or split characters turn into replacement characters
+pub fn parse_bytes(
+    parser: &mut Parser,
+    pending_bytes: &mut Vec<u8>,
+    bytes: &[u8],
+    mut on_sequence: impl FnMut(Vec<Action>, Vec<u8>),
+) {
+    for byte in bytes {
+        pending_bytes.push(*byte);
+
+        let actions = parser.parse_as_vec(&[*byte]);
+        if !actions.is_empty() {
+            on_sequence(actions, take(pending_bytes));
+        }
+    }
+}
+
+/// Decode a complete piece of output into DTOs, like the ones the web UI shows
+pub fn actions_to_dtos(bytes: &[u8]) -> Vec<VteEventDto> {
+    decode_stream(bytes).collect()
+}
+
+/// Decode everything `reader` produces into DTOs, like the ones the web UI shows. Printed text is only
+/// yielded once whatever follows it has been read, so consecutive prints come out as one DTO.
+/// Read errors end the stream
+pub fn decode_stream(reader: impl Read) -> impl Iterator<Item = VteEventDto> {
+    DecodeStream {
+        reader,
+        parser: Parser::new(),
+        pending_bytes: vec![],
+        builder: DtoBuilder::new(false, None, false),
+        dtos: vec![],
+        ready: VecDeque::new(),
+        done: false,
+    }
+}
+
+struct DecodeStream<R> {
+    reader: R,
+    parser: Parser,
+    pending_bytes: Vec<u8>,
+    builder: DtoBuilder,
+    /// DTOs that aren't finished yet; only the last one can still change, by having more text appended
+    dtos: Vec<VteEventDto>,
+    ready: VecDeque<VteEventDto>,
+    done: bool,
+}
+
+impl<R: Read> Iterator for DecodeStream<R> {
+    type Item = VteEventDto;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0u8; 8192];
+        while self.ready.is_empty() && !self.done {
+            let size = match self.reader.read(&mut buf) {
+                Ok(size) => size,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    warn!("error reading output to decode: {e}");
+                    0
+                }
+            };
+            if size == 0 {
+                self.done = true;
+                self.builder.finish_dcs(&mut self.dtos);
+                self.ready.extend(self.dtos.drain(..));
+                break;
+            }
+
+            let (builder, dtos) = (&mut self.builder, &mut self.dtos);
+            parse_bytes(
+                &mut self.parser,
+                &mut self.pending_bytes,
+                &buf[..size],
+                |actions, raw_bytes| {
+                    builder.append_sequence(dtos, actions, raw_bytes);
+                },
+            );
+            let finished = self.dtos.len().saturating_sub(1);
+            self.ready.extend(self.dtos.drain(..finished));
+        }
+        self.ready.pop_front()
+    }
+}
+
+/// Turns actions into DTOs, keeping track of the state that carries over from one action to the next
+pub struct DtoBuilder {
+    fg_color: ColorSpec,
+    bg_color: ColorSpec,
+    last_was_line_break: bool,
+    show_whitespace: bool,
+    /// Replace tabs in printed text with spaces, using tab stops this many columns apart
+    expand_tabs: Option<usize>,
+    /// Collapse runs of cursor movements into one DTO with the net displacement
+    summarize_cursor: bool,
+    /// The cursor movements in the current run, which aren't in `dtos` until something else comes along
+    cursor_run: Option<CursorRun>,
+    /// A rough idea of the cursor position (0-based), so tabs can be expanded to the right width
+    /// and escapes can say where they happened
+    row: usize,
+    column: usize,
+    blink: Blink,
+    underline: Underline,
+    style: TextStyle,
+    /// Whether G0 and G1 are designated as DEC Special Graphics, where ASCII letters draw lines
+    line_drawing: [bool; 2],
+    /// Whether SO has switched GL to G1
+    shifted_out: bool,
+    /// Whether we're between the start and end markers of a bracketed paste
+    pasting: bool,
+    /// The URI of the OSC 8 hyperlink that printed text is part of, if any
+    hyperlink: Option<String>,
+    /// The rows (1-based, inclusive) set with DECSTBM, if it's not the whole screen. A bottom of
+    /// `u32::MAX` means the bottom of the screen
+    scroll_region: Option<(u32, u32)>,
+    /// Whether DECOM is on, making absolute cursor positions relative to the scroll region
+    origin_mode: bool,
+    /// What the last DECSC or SCOSC saved, so restoring can say what comes back
+    saved_cursor: Option<SavedCursor>,
+    /// The DCS string being collected, which isn't in `dtos` until it's over
+    dcs: Option<PendingDcs>,
+}
+
+/// termwiz hands over a DCS string as an `Enter`, then one `Data` per byte, then an `Exit` (and an ST
+/// after that). It's only worth describing once it's all there
+struct PendingDcs {
+    enter: Box<EnterDeviceControlMode>,
+    data: Vec<u8>,
+    raw_bytes: Vec<u8>,
+    /// Whether the `Exit` has come, so only the ST can still be part of it
+    ended: bool,
+}
+
+/// Consecutive cursor movements, collected while `summarize_cursor` is on
+struct CursorRun {
+    /// The DTO for the first move, used as-is if nothing else joins it
+    first: VteEventDto,
+    count: usize,
+    raw_bytes: String,
+    /// Where the cursor was before the first move and after the last one (0-based)
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+/// The state DECSC saves and DECRC brings back: the cursor position, the rendition and the charsets
+#[derive(Clone, Copy)]
+struct SavedCursor {
+    row: usize,
+    column: usize,
+    fg_color: ColorSpec,
+    bg_color: ColorSpec,
+    blink: Blink,
+    underline: Underline,
+    style: TextStyle,
+    origin_mode: bool,
+    line_drawing: [bool; 2],
+    shifted_out: bool,
+}
+
+impl DtoBuilder {
+    pub fn new(show_whitespace: bool, expand_tabs: Option<usize>, summarize_cursor: bool) -> Self {
+        Self {
+            fg_color: ColorSpec::Default,
+            bg_color: ColorSpec::Default,
+            last_was_line_break: false,
+            show_whitespace,
+            expand_tabs,
+            summarize_cursor,
+            cursor_run: None,
+            row: 0,
+            column: 0,
+            blink: Blink::None,
+            underline: Underline::None,
+            style: TextStyle::default(),
+            line_drawing: [false; 2],
+            shifted_out: false,
+            pasting: false,
+            hyperlink: None,
+            scroll_region: None,
+            origin_mode: false,
+            saved_cursor: None,
+            dcs: None,
+        }
+    }
+
+    /// Append the DTOs for all the actions parsed from one byte sequence to `dtos`, and return what should be sent
+    /// to clients that are already connected. termwiz splits an SGR that sets several attributes (like `CSI 1;31;4m`)
+    /// into one action per attribute; those get grouped back together into one DTO
+    pub fn append_sequence(
+        &mut self,
+        dtos: &mut Vec<VteEventDto>,
+        actions: Vec<Action>,
+        raw_bytes: Vec<u8>,
+    ) -> Vec<VteEventDto> {
+        if actions.len() > 1
+            && actions
+                .iter()
+                .all(|action| matches!(action, Action::CSI(CSI::Sgr(_))))
+        {
+            let mut attributes = vec![];
+            for action in actions {
+                self.update_styles(&action);
+                let tuple = (action, raw_bytes.clone());
+                if let VteEventDto::GenericEscape { tooltip, .. }
+                | VteEventDto::ColorEscape { tooltip, .. } = VteEventDto::from(&tuple)
+                {
+                    attributes.extend(tooltip);
+                }
+            }
+            let dto = VteEventDto::Sgr {
+                attributes,
+                raw_bytes: sanitize_raw_bytes(&raw_bytes),
+            };
+            let mut new_dtos = self.finish_dcs(dtos);
+            new_dtos.extend(self.finish_cursor_run(dtos));
+            new_dtos.extend(self.push(dtos, dto));
+            return new_dtos;
+        }
+
+        let mut new_dtos = vec![];
+        for action in actions {
+            if matches!(
+                action,
+                Action::DeviceControl(
+                    DeviceControlMode::Enter(_) | DeviceControlMode::ShortDeviceControl(_)
+                )
+            ) {
+                new_dtos.extend(self.finish_dcs(dtos));
+                new_dtos.extend(self.finish_cursor_run(dtos));
+            }
+            let Some(action) = self.collect_dcs(action, &raw_bytes) else {
+                continue;
+            };
+            // anything else ends the DCS string, even if there was no ST
+            new_dtos.extend(self.finish_dcs(dtos));
+            new_dtos.extend(self.append(dtos, action, raw_bytes.clone()));
+        }
+        new_dtos
+    }
+
+    /// Start collecting a DCS string, or add to the one being collected. Actions that aren't part of one
+    /// are handed back
+    fn collect_dcs(&mut self, action: Action, raw_bytes: &[u8]) -> Option<Action> {
+        let Some(pending) = &mut self.dcs else {
+            self.dcs = Some(match action {
+                Action::DeviceControl(DeviceControlMode::Enter(enter)) => PendingDcs {
+                    enter,
+                    data: vec![],
+                    raw_bytes: raw_bytes.to_vec(),
+                    ended: false,
+                },
+                // termwiz hands over short strings in one go, but their ST still comes separately
+                Action::DeviceControl(DeviceControlMode::ShortDeviceControl(short)) => PendingDcs {
+                    enter: Box::new(EnterDeviceControlMode {
+                        byte: short.byte,
+                        params: short.params,
+                        intermediates: short.intermediates,
+                        ignored_extra_intermediates: false,
+                    }),
+                    data: short.data,
+                    raw_bytes: raw_bytes.to_vec(),
+                    ended: true,
+                },
+                action => return Some(action),
+            });
+            return None;
+        };
+        match action {
+            Action::DeviceControl(DeviceControlMode::Data(byte)) if !pending.ended => {
+                pending.data.push(byte)
+            }
+            Action::DeviceControl(DeviceControlMode::Exit) if !pending.ended => {
+                pending.ended = true
+            }
+            Action::Esc(Esc::Code(EscCode::StringTerminator)) if pending.ended => {}
+            action => return Some(action),
+        }
+        pending.raw_bytes.extend_from_slice(raw_bytes);
+        None
+    }
+
+    /// Whether a DCS string is being collected. It's added to `dtos` by the next call that appends anything
+    /// else, or by `finish_dcs`
+    pub fn holding_dcs(&self) -> bool {
+        self.dcs.is_some()
+    }
+
+    /// Append the DTO for the DCS string being collected, if there is one, and return what should be sent to
+    /// clients that are already connected. A string that never ended is described as far as it got
+    pub fn finish_dcs(&mut self, dtos: &mut Vec<VteEventDto>) -> Vec<VteEventDto> {
+        let Some(pending) = self.dcs.take() else {
+            return vec![];
+        };
+        self.push(dtos, dcs_string_to_dto(&pending))
+    }
+
+    /// Append the DTO for an action to `dtos` and return what should be sent to clients that are already connected
+    fn append(
+        &mut self,
+        dtos: &mut Vec<VteEventDto>,
+        action: Action,
+        raw_bytes: Vec<u8>,
+    ) -> Vec<VteEventDto> {
+        // update global colours if needed and build the DTO for this action
+        self.update_styles(&action);
+        let tab_expansion = match (&action, self.expand_tabs) {
+            (Action::Control(ControlCode::HorizontalTab), Some(stop)) if stop > 0 => {
+                Some(stop - self.column % stop)
+            }
+            _ => None,
+        };
+        let position = CursorPosition {
+            cursor_row: self.row + 1,
+            cursor_col: self.column + 1,
+        };
+        let start = (self.row, self.column);
+        let is_cursor_move = self.summarize_cursor && is_cursor_move(&action);
+        let note = self
+            .origin_note(&action)
+            .or_else(|| self.restore_note(&action));
+        self.update_cursor(&action);
+        self.update_saved_cursor(&action);
+        self.update_margins(&action);
+        self.update_charsets(&action);
+        self.update_paste(&action);
+        self.update_hyperlink(&action);
+        let tuple = (action, raw_bytes);
+        let mut dto = VteEventDto::from(&tuple);
+        if let (
+            Some(note),
+            VteEventDto::GenericEscape {
+                tooltip: Some(tooltip),
+                ..
+            },
+        ) = (note, &mut dto)
+        {
+            tooltip.push_str(&note);
+        }
+        self.update_print(&mut dto);
+        if let VteEventDto::GenericEscape { cursor, .. } | VteEventDto::ColorEscape { cursor, .. } =
+            &mut dto
+        {
+            *cursor = Some(position);
+        }
+
+        if is_cursor_move {
+            let end = (self.row, self.column);
+            let raw_bytes = match &dto {
+                VteEventDto::GenericEscape { raw_bytes, .. } => raw_bytes.clone(),
+                _ => String::new(),
+            };
+            match &mut self.cursor_run {
+                Some(run) => {
+                    run.count += 1;
+                    run.raw_bytes.push_str(&raw_bytes);
+                    run.end = end;
+                }
+                None => {
+                    self.cursor_run = Some(CursorRun {
+                        first: dto,
+                        count: 1,
+                        raw_bytes,
+                        start,
+                        end,
+                    })
+                }
+            }
+            return vec![];
+        }
+
+        let mut new_dtos = self.finish_cursor_run(dtos);
+        if let Some(spaces) = tab_expansion {
+            // the spaces go in the printed text, but the tab itself stays visible as an escape. When
+            // whitespace is shown, its first cell is an arrow so it can be told apart from spaces
+            let arrow = if self.show_whitespace { "→" } else { " " };
+            let mut spaces_dto = VteEventDto::Print {
+                string: format!("{arrow}{}", " ".repeat(spaces - 1)),
+                color: None,
+                bg_color: None,
+                show_whitespace: false,
+                blink: None,
+                underline: None,
+                style: TextStyle::default(),
+                pasted: false,
+                hyperlink: None,
+            };
+            self.update_print(&mut spaces_dto);
+            new_dtos.extend(self.push(dtos, spaces_dto));
+            if let VteEventDto::GenericEscape { tooltip, .. } = &mut dto {
+                *tooltip = Some(format!("Tab (expanded to {spaces} spaces)"));
+            }
+        }
+        new_dtos.extend(self.push(dtos, dto));
+        new_dtos
+    }
+
+    /// Whether cursor movements are being held back to be summarized. They're added to `dtos` by the next
+    /// call that appends anything else, or by `finish_cursor_run`
+    pub fn holding_cursor_run(&self) -> bool {
+        self.cursor_run.is_some()
+    }
+
+    /// Append the DTO for the current run of cursor movements, if there is one, and return what should be
+    /// sent to clients that are already connected
+    pub fn finish_cursor_run(&mut self, dtos: &mut Vec<VteEventDto>) -> Vec<VteEventDto> {
+        let Some(run) = self.cursor_run.take() else {
+            return vec![];
+        };
+        if run.count == 1 {
+            return self.push(dtos, run.first);
+        }
+        let rows = run.end.0 as i64 - run.start.0 as i64;
+        let columns = run.end.1 as i64 - run.start.1 as i64;
+        let mut net = vec![];
+        match rows {
+            0 => {}
+            rows if rows > 0 => net.push(format!("down {rows}")),
+            rows => net.push(format!("up {}", -rows)),
+        }
+        match columns {
+            0 => {}
+            columns if columns > 0 => net.push(format!("right {columns}")),
+            columns => net.push(format!("left {}", -columns)),
+        }
+        let net = if net.is_empty() {
+            "back where it started".into()
+        } else {
+            net.join(", ")
+        };
+        let dto = VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("cursor"),
+            tooltip: Some(format!("{} cursor moves, net: {net}", run.count)),
+            raw_bytes: run.raw_bytes,
+            cursor: Some(CursorPosition {
+                cursor_row: run.start.0 + 1,
+                cursor_col: run.start.1 + 1,
+            }),
+        };
+        self.push(dtos, dto)
+    }
+
+    fn update_styles(&mut self, action: &Action) {
+        update_global_colors(action, &mut self.fg_color, &mut self.bg_color);
+        match action {
+            Action::CSI(CSI::Sgr(Sgr::Blink(blink))) => self.blink = *blink,
+            Action::CSI(CSI::Sgr(Sgr::Underline(underline))) => self.underline = *underline,
+            // bold and dim are two settings of the same attribute, so turning one on turns the other off
+            Action::CSI(CSI::Sgr(Sgr::Intensity(intensity))) => {
+                self.style.bold = *intensity == Intensity::Bold;
+                self.style.dim = *intensity == Intensity::Half;
+            }
+            Action::CSI(CSI::Sgr(Sgr::Italic(on))) => self.style.italic = *on,
+            Action::CSI(CSI::Sgr(Sgr::StrikeThrough(on))) => self.style.strikethrough = *on,
+            Action::CSI(CSI::Sgr(Sgr::Inverse(on))) => self.style.inverse = *on,
+            Action::CSI(CSI::Sgr(Sgr::Reset)) => {
+                self.blink = Blink::None;
+                self.underline = Underline::None;
+                self.style = TextStyle::default();
+            }
+            _ => {}
+        }
+    }
+
+    fn update_print(&self, dto: &mut VteEventDto) {
+        update_print_colors(dto, self.fg_color, self.bg_color);
+        if let VteEventDto::Print {
+            string,
+            color,
+            bg_color,
+            show_whitespace,
+            blink,
+            underline,
+            style,
+            pasted,
+            hyperlink,
+        } = dto
+        {
+            *style = self.style;
+            if self.style.inverse {
+                let fg = color.take().unwrap_or(DEFAULT_FG.into());
+                let bg = bg_color.take().unwrap_or(DEFAULT_BG.into());
+                *color = Some(bg);
+                *bg_color = Some(fg);
+            }
+            *pasted = self.pasting;
+            hyperlink.clone_from(&self.hyperlink);
+            if self.line_drawing[self.shifted_out as usize] {
+                *string = string.chars().map(dec_special_graphics).collect();
+            }
+            *show_whitespace = self.show_whitespace;
+            *blink = match self.blink {
+                Blink::None => None,
+                Blink::Slow => Some("slow".into()),
+                Blink::Rapid => Some("rapid".into()),
+            };
+            *underline = underline_style(self.underline).map(Into::into);
+        }
+    }
+
+    fn update_charsets(&mut self, action: &Action) {
+        match action {
+            Action::Esc(Esc::Code(code)) => match code {
+                EscCode::DecLineDrawingG0 => self.line_drawing[0] = true,
+                EscCode::AsciiCharacterSetG0 | EscCode::UkCharacterSetG0 => {
+                    self.line_drawing[0] = false
+                }
+                EscCode::DecLineDrawingG1 => self.line_drawing[1] = true,
+                EscCode::AsciiCharacterSetG1 | EscCode::UkCharacterSetG1 => {
+                    self.line_drawing[1] = false
+                }
+                EscCode::FullReset => {
+                    self.line_drawing = [false; 2];
+                    self.shifted_out = false;
+                }
+                _ => {}
+            },
+            Action::Control(ControlCode::ShiftOut) => self.shifted_out = true,
+            Action::Control(ControlCode::ShiftIn) => self.shifted_out = false,
+            _ => {}
+        }
+    }
+
+    fn update_paste(&mut self, action: &Action) {
+        if let Action::CSI(CSI::Unspecified(unspecified)) = action {
+            match (unspecified.params.as_slice(), unspecified.control) {
+                ([CsiParam::Integer(200)], '~') => self.pasting = true,
+                ([CsiParam::Integer(201)], '~') => self.pasting = false,
+                _ => {}
+            }
+        }
+    }
+
+    fn update_hyperlink(&mut self, action: &Action) {
+        match action {
+            Action::OperatingSystemCommand(osc) => match &**osc {
+                // an empty URI ends the link too, whatever the params say
+                OperatingSystemCommand::SetHyperlink(Some(link)) if !link.uri().is_empty() => {
+                    self.hyperlink = Some(link.uri().into())
+                }
+                OperatingSystemCommand::SetHyperlink(_) => self.hyperlink = None,
+                _ => {}
+            },
+            Action::Esc(Esc::Code(EscCode::FullReset)) => self.hyperlink = None,
+            _ => {}
+        }
+    }
+
+    /// Absolute cursor positions are relative to the scroll region in origin mode, which is easy to miss
+    /// when reading a capture
+    fn origin_note(&self, action: &Action) -> Option<String> {
+        match (self.origin_mode, self.scroll_region, action) {
+            (
+                true,
+                Some((top, bottom)),
+                Action::CSI(CSI::Cursor(
+                    Cursor::Position { .. }
+                    | Cursor::CharacterAndLinePosition { .. }
+                    | Cursor::LinePositionAbsolute(_),
+                )),
+            ) => Some(format!(
+                " (relative to scroll region {})",
+                scroll_region_rows(top, bottom)
+            )),
+            _ => None,
+        }
+    }
+
+    /// Restoring the cursor can change colors and charsets as well as the position, so spell out what
+    /// was saved
+    fn restore_note(&self, action: &Action) -> Option<String> {
+        if !is_restore_cursor(action) {
+            return None;
+        }
+        let Some(saved) = self.saved_cursor else {
+            return Some(
+                ": nothing was saved, so this moves home and resets the attributes".into(),
+            );
+        };
+        let mut parts = vec![
+            format!("row {}, column {}", saved.row + 1, saved.column + 1),
+            format!(
+                "foreground {}",
+                hex_color(&saved.fg_color).unwrap_or("default".into())
+            ),
+            format!(
+                "background {}",
+                hex_color(&saved.bg_color).unwrap_or("default".into())
+            ),
+        ];
+        match saved.blink {
+            Blink::None => {}
+            Blink::Slow => parts.push("slow blink".into()),
+            Blink::Rapid => parts.push("rapid blink".into()),
+        }
+        if let Some(style) = underline_style(saved.underline) {
+            parts.push(format!("{style} underline"));
+        }
+        for (on, name) in [
+            (saved.style.bold, "bold"),
+            (saved.style.dim, "dim"),
+            (saved.style.italic, "italic"),
+            (saved.style.strikethrough, "strikethrough"),
+            (saved.style.inverse, "inverse"),
+        ] {
+            if on {
+                parts.push(name.into());
+            }
+        }
+        if saved.origin_mode {
+            parts.push("origin mode".into());
+        }
+        if saved.line_drawing[saved.shifted_out as usize] {
+            parts.push("line drawing charset".into());
+        }
+        Some(format!(": {}", parts.join(", ")))
+    }
+
+    fn update_saved_cursor(&mut self, action: &Action) {
+        match action {
+            Action::Esc(Esc::Code(EscCode::DecSaveCursorPosition))
+            | Action::CSI(CSI::Cursor(Cursor::SaveCursor)) => {
+                self.saved_cursor = Some(SavedCursor {
+                    row: self.row,
+                    column: self.column,
+                    fg_color: self.fg_color,
+                    bg_color: self.bg_color,
+                    blink: self.blink,
+                    underline: self.underline,
+                    style: self.style,
+                    origin_mode: self.origin_mode,
+                    line_drawing: self.line_drawing,
+                    shifted_out: self.shifted_out,
+                })
+            }
+            action if is_restore_cursor(action) => {
+                // with nothing saved, terminals go home and reset the rendition and charsets
+                let saved = self.saved_cursor.unwrap_or(SavedCursor {
+                    row: 0,
+                    column: 0,
+                    fg_color: ColorSpec::Default,
+                    bg_color: ColorSpec::Default,
+                    blink: Blink::None,
+                    underline: Underline::None,
+                    style: TextStyle::default(),
+                    origin_mode: false,
+                    line_drawing: [false; 2],
+                    shifted_out: false,
+                });
+                self.row = saved.row;
+                self.column = saved.column;
+                self.fg_color = saved.fg_color;
+                self.bg_color = saved.bg_color;
+                self.blink = saved.blink;
+                self.underline = saved.underline;
+                self.style = saved.style;
+                self.origin_mode = saved.origin_mode;
+                self.line_drawing = saved.line_drawing;
+                self.shifted_out = saved.shifted_out;
+            }
+            Action::Esc(Esc::Code(EscCode::FullReset)) => self.saved_cursor = None,
+            _ => {}
+        }
+    }
+
+    /// The row that absolute cursor positions are counted from (0-based)
+    fn origin_row(&self) -> usize {
+        match (self.origin_mode, self.scroll_region) {
+            (true, Some((top, _))) => top as usize - 1,
+            _ => 0,
+        }
+    }
+
+    fn update_margins(&mut self, action: &Action) {
+        match action {
+            Action::CSI(CSI::Cursor(Cursor::SetTopAndBottomMargins { top, bottom })) => {
+                let (top, bottom) = (top.as_one_based(), bottom.as_one_based());
+                self.scroll_region = if top <= 1 && bottom == u32::MAX {
+                    None
+                } else {
+                    Some((top.max(1), bottom))
+                };
+                // DECSTBM also moves the cursor home
+                self.row = self.origin_row();
+                self.column = 0;
+            }
+            Action::CSI(CSI::Mode(
+                Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Select132Columns))
+                | Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                    DecPrivateModeCode::Select132Columns,
+                )),
+            )) => {
+                self.scroll_region = None;
+                self.row = 0;
+                self.column = 0;
+            }
+            Action::CSI(CSI::Mode(mode)) => {
+                self.origin_mode = match mode {
+                    Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                        DecPrivateModeCode::OriginMode,
+                    )) => true,
+                    Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                        DecPrivateModeCode::OriginMode,
+                    )) => false,
+                    _ => return,
+                };
+                // so does switching origin mode
+                self.row = self.origin_row();
+                self.column = 0;
+            }
+            Action::Esc(Esc::Code(EscCode::FullReset)) => {
+                self.scroll_region = None;
+                self.origin_mode = false;
+            }
+            Action::CSI(CSI::Device(device)) if matches!(**device, Device::SoftReset) => {
+                self.scroll_region = None;
+                self.origin_mode = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn update_cursor(&mut self, action: &Action) {
+        match action {
+            Action::Print(_) => self.column += 1,
+            Action::PrintString(s) => self.column += s.chars().count(),
+            Action::Control(ControlCode::CarriageReturn) => self.column = 0,
+            Action::Control(ControlCode::LineFeed) => {
+                // assume the pty translates LF to CRLF, like it does by default
+                self.row += 1;
+                self.column = 0;
+            }
+            Action::Control(ControlCode::VerticalTab | ControlCode::FormFeed) => self.row += 1,
+            Action::Control(ControlCode::Backspace) => self.column = self.column.saturating_sub(1),
+            Action::Control(ControlCode::HorizontalTab) => {
+                let stop = self.expand_tabs.filter(|stop| *stop > 0).unwrap_or(8);
+                self.column += stop - self.column % stop;
+            }
+            Action::CSI(CSI::Cursor(cursor)) => match cursor {
+                Cursor::Position { line, col } | Cursor::CharacterAndLinePosition { line, col } => {
+                    self.row = self.origin_row() + line.as_zero_based() as usize;
+                    self.column = col.as_zero_based() as usize;
+                }
+                Cursor::CharacterAbsolute(col) | Cursor::CharacterPositionAbsolute(col) => {
+                    self.column = col.as_zero_based() as usize
+                }
+                Cursor::LinePositionAbsolute(line) => {
+                    self.row = self.origin_row() + (*line as usize).saturating_sub(1)
+                }
+                Cursor::Up(n) | Cursor::LinePositionBackward(n) => {
+                    self.row = self.row.saturating_sub(*n as usize)
+                }
+                Cursor::Down(n) | Cursor::LinePositionForward(n) => self.row += *n as usize,
+                Cursor::PrecedingLine(n) => {
+                    self.row = self.row.saturating_sub(*n as usize);
+                    self.column = 0;
+                }
+                Cursor::NextLine(n) => {
+                    self.row += *n as usize;
+                    self.column = 0;
+                }
+                Cursor::Left(n) | Cursor::CharacterPositionBackward(n) => {
+                    self.column = self.column.saturating_sub(*n as usize)
+                }
+                Cursor::Right(n) | Cursor::CharacterPositionForward(n) => {
+                    self.column += *n as usize
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, dtos: &mut Vec<VteEventDto>, dto: VteEventDto) -> Vec<VteEventDto> {
+        // optimization: if the last DTO was a print and this is a print, concatenate them
+        // this greatly cuts down on the number of events sent to the front-end
+        if let Some(last) = dtos.last_mut() {
+            if last.merge_print(&dto) {
+                return vec![dto];
+            }
+        }
+
+        // emit an invisible line break DTO if we're transitioning from a line break to a non-line break or vice versa
+        let is_line_break = matches!(&dto, VteEventDto::LineBreak { .. });
+        let new_dtos = if is_line_break != self.last_was_line_break {
+            vec![VteEventDto::InvisibleLineBreak {}, dto]
+        } else {
+            vec![dto]
+        };
+        self.last_was_line_break = is_line_break;
+
+        dtos.extend(new_dtos.iter().cloned());
+        new_dtos
+    }
+}
+
+pub fn hex_color(color: &ColorSpec) -> Option<String> {
+    match color {
+        ColorSpec::Default => None,
+        ColorSpec::PaletteIndex(i) => {
+            let (r, g, b) = rgb_from_ansi256(*i);
+            Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+        }
+        ColorSpec::TrueColor(srgba) => Some(srgba.to_rgb_string()),
+    }
+}
+
+/// Relative and absolute cursor moves, leaving out saves, restores and reports
+pub fn is_cursor_move(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::CSI(CSI::Cursor(
+            Cursor::Position { .. }
+                | Cursor::CharacterAndLinePosition { .. }
+                | Cursor::CharacterAbsolute(_)
+                | Cursor::CharacterPositionAbsolute(_)
+                | Cursor::LinePositionAbsolute(_)
+                | Cursor::Up(_)
+                | Cursor::Down(_)
+                | Cursor::Left(_)
+                | Cursor::Right(_)
+                | Cursor::LinePositionBackward(_)
+                | Cursor::LinePositionForward(_)
+                | Cursor::CharacterPositionBackward(_)
+                | Cursor::CharacterPositionForward(_)
+                | Cursor::NextLine(_)
+                | Cursor::PrecedingLine(_)
+        ))
+    )
+}
+
+fn is_restore_cursor(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::Esc(Esc::Code(EscCode::DecRestoreCursorPosition))
+            | Action::CSI(CSI::Cursor(Cursor::RestoreCursor))
+    )
+}
+
+fn update_print_colors(dto: &mut VteEventDto, fg_color: ColorSpec, bg_color: ColorSpec) {
+    if let VteEventDto::Print {
+        color: dto_color,
+        bg_color: dto_bg_color,
+        ..
+    } = dto
+    {
+        *dto_color = hex_color(&fg_color);
+        *dto_bg_color = hex_color(&bg_color);
+        // The UI's default text color can be unreadable on whatever background the program picked
+        if fg_color == ColorSpec::Default {
+            if let Some(bg) = rgb(&bg_color) {
+                *dto_color = Some(contrasting_color(bg).into());
+            }
+        }
+    }
+}
+
+fn rgb(color: &ColorSpec) -> Option<(u8, u8, u8)> {
+    match color {
+        ColorSpec::Default => None,
+        ColorSpec::PaletteIndex(i) => Some(rgb_from_ansi256(*i)),
+        ColorSpec::TrueColor(srgba) => {
+            let (r, g, b, _) = srgba.to_srgb_u8();
+            Some((r, g, b))
+        }
+    }
+}
+
+/// Black or white, whichever is easier to read on `background`, going by WCAG relative luminance.
+/// 0.179 is where the contrast ratio against black and against white comes out the same
+fn contrasting_color((r, g, b): (u8, u8, u8)) -> &'static str {
+    let linear = |channel: u8| {
+        let c = channel as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let luminance = 0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b);
+    if luminance > 0.179 {
+        "#000000"
+    } else {
+        "#ffffff"
+    }
+}
+
+/// The web UI's own text and background colors, which stand in for the terminal's defaults when inverse
+/// video swaps them
+const DEFAULT_FG: &str = "#f9fafb";
+const DEFAULT_BG: &str = "#0f172a";
+
+fn underline_style(underline: Underline) -> Option<&'static str> {
+    match underline {
+        Underline::None => None,
+        Underline::Single => Some("single"),
+        Underline::Double => Some("double"),
+        Underline::Curly => Some("curly"),
+        Underline::Dotted => Some("dotted"),
+        Underline::Dashed => Some("dashed"),
+    }
+}
+
+/// What a character looks like when the DEC Special Graphics charset is active. It's mostly used for
+/// drawing boxes, e.g. `lqqk` is the top of a box
+fn dec_special_graphics(c: char) -> char {
+    match c {
+        '`' => '◆',
+        'a' => '▒',
+        'b' => '␉',
+        'c' => '␌',
+        'd' => '␍',
+        'e' => '␊',
+        'f' => '°',
+        'g' => '±',
+        'h' => '␤',
+        'i' => '␋',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        c => c,
+    }
+}
+
+fn update_global_colors(action: &Action, fg_color: &mut ColorSpec, bg_color: &mut ColorSpec) {
+    if let Action::CSI(CSI::Sgr(sgr)) = action {
+        match sgr {
+            Sgr::Foreground(color) => {
+                *fg_color = *color;
+            }
+            Sgr::Background(color) => {
+                *bg_color = *color;
+            }
+            Sgr::Reset => {
+                *fg_color = ColorSpec::Default;
+                *bg_color = ColorSpec::Default;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Where the cursor was (1-based, like CUP) when an escape sequence was emitted, as far as we can tell
+/// without emulating a whole terminal
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct CursorPosition {
+    cursor_row: usize,
+    cursor_col: usize,
+}
+
+/// The SGR attributes of printed text that are simply on or off. Blinking and underlines come in several
+/// kinds, so they're tracked separately
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    /// Already applied by swapping the colors, so clients don't need it
+    #[serde(skip)]
+    pub inverse: bool,
+}
+
+/// Where an OSC 133 shell integration marker falls in the cycle of prompt, command and output
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptMarkerKind {
+    PromptStart,
+    CommandStart,
+    OutputStart,
+    CommandFinished,
+}
+
+impl PromptMarkerKind {
+    fn description(self) -> &'static str {
+        match self {
+            PromptMarkerKind::PromptStart => "Prompt starts",
+            PromptMarkerKind::CommandStart => "Command starts (end of prompt)",
+            PromptMarkerKind::OutputStart => "Command output starts",
+            PromptMarkerKind::CommandFinished => "Command finished",
+        }
+    }
+}
+
+/// Which recording an event in `escape-artist diff --web` is missing from
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffSide {
+    /// Only in the original recording
+    Removed,
+    /// Only in the recording it's compared to
+    Added,
+}
+
+impl DiffSide {
+    pub fn sign(&self) -> char {
+        match self {
+            DiffSide::Removed => '-',
+            DiffSide::Added => '+',
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type")] // give each JSON record a "type" field indicating the enum type, easier to consume from JS
+pub enum VteEventDto {
+    Print {
+        string: String,
+        color: Option<String>,
+        bg_color: Option<String>,
+        /// Whether the front-end should render whitespace visibly
+        show_whitespace: bool,
+        /// "slow" or "rapid" if the text is blinking (SGR 5/6)
+        blink: Option<String>,
+        /// "single", "double", "curly", "dotted" or "dashed" if the text is underlined (SGR 4, 21 or 4:n)
+        underline: Option<String>,
+        #[serde(flatten)]
+        style: TextStyle,
+        /// Whether the text came between bracketed paste markers, i.e. the user pasted it and it was echoed back
+        pasted: bool,
+        /// The URI of the OSC 8 hyperlink the text is part of
+        hyperlink: Option<String>,
+    },
+    GenericEscape {
+        title: Option<String>,
+        icon_svg: Option<String>,
+        tooltip: Option<String>,
+        raw_bytes: String,
+        #[serde(flatten)]
+        cursor: Option<CursorPosition>,
+    },
+    ColorEscape {
+        title: Option<String>,
+        icon_svg: Option<String>,
+        tooltip: Option<String>,
+        color: String,
+        raw_bytes: String,
+        #[serde(flatten)]
+        cursor: Option<CursorPosition>,
+    },
+    InvisibleLineBreak {},
+    /// Tells clients to throw away everything they've been sent so far. Never stored in `all_dtos`
+    Clear {},
+    /// A note a client attached to the DTO at `target_id` in `all_dtos`. Kept in `AppState::annotations`
+    /// rather than `all_dtos`, so that it doesn't shift the indices of the DTOs after it
+    Annotation {
+        target_id: usize,
+        note: String,
+    },
+    LineBreak {
+        title: String,
+    },
+    SessionEnded {
+        exit_code: Option<i32>,
+        sequence_count: i64,
+    },
+    /// Decoding stopped after `limit` escape sequences because of `--head`; the program kept running
+    Truncated {
+        limit: usize,
+    },
+    /// The oldest `count` events were dropped to stay under `--max-events`. Never stored in `all_dtos`;
+    /// clients get it ahead of the events that are left
+    EventsDropped {
+        count: usize,
+    },
+    /// A websocket client fell behind and missed `count` live events. Never stored in `all_dtos`
+    Skipped {
+        count: u64,
+    },
+    /// An OSC 133 shell integration marker, which splits a session up into commands. Shells don't
+    /// have to send all four kinds, and only some send an exit code with `CommandFinished`
+    PromptMarker {
+        kind: PromptMarkerKind,
+        exit_code: Option<i32>,
+        raw_bytes: String,
+    },
+    /// One SGR sequence that sets several attributes at once
+    Sgr {
+        attributes: Vec<String>,
+        raw_bytes: String,
+    },
+    /// A Sixel or Kitty image we could decode, as a `data:` URL. Images that are too big or that we
+    /// can't read come through as a `GenericEscape` instead
+    Image {
+        title: String,
+        tooltip: String,
+        data_url: String,
+        width: u32,
+        height: u32,
+        raw_bytes: String,
+    },
+    /// An event that only one of the recordings compared by `escape-artist diff --web` has. Events
+    /// both recordings have are sent as they are
+    Diff {
+        side: DiffSide,
+        event: Box<VteEventDto>,
+    },
+}
+
+impl VteEventDto {
+    /// Append `other`'s text to this DTO if both are prints that look the same. Text with different
+    /// colors or attributes stays in separate DTOs so each one renders correctly
+    pub fn merge_print(&mut self, other: &VteEventDto) -> bool {
+        match (self, other) {
+            (
+                VteEventDto::Print {
+                    string: last_string,
+                    color: last_color,
+                    bg_color: last_bg_color,
+                    show_whitespace: last_show_whitespace,
+                    blink: last_blink,
+                    underline: last_underline,
+                    style: last_style,
+                    pasted: last_pasted,
+                    hyperlink: last_hyperlink,
+                },
+                VteEventDto::Print {
+                    string,
+                    color,
+                    bg_color,
+                    show_whitespace,
+                    blink,
+                    underline,
+                    style,
+                    pasted,
+                    hyperlink,
+                },
+            ) if *last_color == *color
+                && *last_bg_color == *bg_color
+                && *last_show_whitespace == *show_whitespace
+                && *last_blink == *blink
+                && *last_underline == *underline
+                && *last_style == *style
+                && *last_pasted == *pasted
+                && *last_hyperlink == *hyperlink =>
+            {
+                last_string.push_str(string);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// A one-line plain text description, used by the `explain` and `check` subcommands
+    pub fn explain(&self) -> Option<String> {
+        match self {
+            VteEventDto::Print {
+                string,
+                pasted,
+                hyperlink,
+                ..
+            } => {
+                let mut explanation = format!("Print {string:?}");
+                if *pasted {
+                    explanation.push_str(" (pasted)");
+                }
+                if let Some(uri) = hyperlink {
+                    explanation.push_str(&format!(" (linked to {uri})"));
+                }
+                Some(explanation)
+            }
+            VteEventDto::GenericEscape {
+                title,
+                tooltip,
+                raw_bytes,
+                ..
+            }
+            | VteEventDto::ColorEscape {
+                title,
+                tooltip,
+                raw_bytes,
+                ..
+            } => {
+                let description = tooltip.as_ref().or(title.as_ref());
+                Some(format!(
+                    "{raw_bytes:?}: {}",
+                    description.map(String::as_str).unwrap_or("")
+                ))
+            }
+            VteEventDto::InvisibleLineBreak {} | VteEventDto::Clear {} => None,
+            VteEventDto::Annotation { target_id, note } => {
+                Some(format!("Note on event {target_id}: {note}"))
+            }
+            VteEventDto::LineBreak { title } => Some(format!("{title} (line break)")),
+            VteEventDto::SessionEnded { exit_code, .. } => {
+                Some(format!("Session ended with exit code {exit_code:?}"))
+            }
+            VteEventDto::Truncated { limit } => Some(format!(
+                "Stopped decoding after {limit} escape sequences (--head)"
+            )),
+            VteEventDto::EventsDropped { count } => Some(format!(
+                "{count} earlier events were dropped (--max-events)"
+            )),
+            VteEventDto::Skipped { count } => Some(format!(
+                "{count} events were skipped because the client fell behind"
+            )),
+            VteEventDto::PromptMarker { raw_bytes, .. } => {
+                Some(format!("{raw_bytes:?}: {}", self.prompt_marker_tooltip()))
+            }
+            VteEventDto::Sgr {
+                attributes,
+                raw_bytes,
+            } => Some(format!("{raw_bytes:?}: {}", attributes.join("; "))),
+            VteEventDto::Image {
+                tooltip, raw_bytes, ..
+            } => Some(format!("{raw_bytes:?}: {tooltip}")),
+            VteEventDto::Diff { side, event } => event
+                .explain()
+                .map(|explanation| format!("{} {explanation}", side.sign())),
+        }
+    }
+
+    /// e.g. "Command finished with exit code 1"; empty for anything but a `PromptMarker`
+    pub fn prompt_marker_tooltip(&self) -> String {
+        match self {
+            VteEventDto::PromptMarker {
+                kind,
+                exit_code: Some(exit_code),
+                ..
+            } => format!("{} with exit code {exit_code}", kind.description()),
+            VteEventDto::PromptMarker { kind, .. } => kind.description().into(),
+            _ => String::new(),
+        }
+    }
+
+    pub fn without_cursor(&self) -> VteEventDto {
+        let mut dto = self.clone();
+        if let VteEventDto::GenericEscape { cursor, .. } | VteEventDto::ColorEscape { cursor, .. } =
+            &mut dto
+        {
+            *cursor = None;
+        }
+        dto
+    }
+
+    /// (title, tooltip, raw bytes) for `explain --output-format csv`
+    pub fn csv_fields(&self) -> (&str, String, &str) {
+        match self {
+            VteEventDto::Print { string, .. } => ("", String::new(), string),
+            VteEventDto::GenericEscape {
+                title,
+                tooltip,
+                raw_bytes,
+                ..
+            }
+            | VteEventDto::ColorEscape {
+                title,
+                tooltip,
+                raw_bytes,
+                ..
+            } => (
+                title.as_deref().unwrap_or(""),
+                tooltip.clone().unwrap_or_default(),
+                raw_bytes,
+            ),
+            VteEventDto::LineBreak { title } => (title, "Line break".into(), ""),
+            VteEventDto::Sgr {
+                attributes,
+                raw_bytes,
+            } => ("SGR", attributes.join("; "), raw_bytes),
+            VteEventDto::Image {
+                title,
+                tooltip,
+                raw_bytes,
+                ..
+            } => (title, tooltip.clone(), raw_bytes),
+            VteEventDto::Diff { event, .. } => event.csv_fields(),
+            VteEventDto::Annotation { note, .. } => ("Note", note.clone(), ""),
+            VteEventDto::PromptMarker { raw_bytes, .. } => {
+                ("OSC 133", self.prompt_marker_tooltip(), raw_bytes)
+            }
+            VteEventDto::InvisibleLineBreak {}
+            | VteEventDto::Clear {}
+            | VteEventDto::SessionEnded { .. }
+            | VteEventDto::Truncated { .. }
+            | VteEventDto::EventsDropped { .. }
+            | VteEventDto::Skipped { .. } => ("", String::new(), ""),
+        }
+    }
+}
+
+impl From<&(Action, Vec<u8>)> for VteEventDto {
+    fn from(value: &(Action, Vec<u8>)) -> Self {
+        let (action, raw_bytes) = value;
+        match action {
+            Action::Print(c) => VteEventDto::Print {
+                string: c.to_string(),
+                color: None,
+                bg_color: None,
+                show_whitespace: false,
+                blink: None,
+                underline: None,
+                style: TextStyle::default(),
+                pasted: false,
+                hyperlink: None,
+            },
+            Action::PrintString(s) => VteEventDto::Print {
+                string: s.clone(),
+                color: None,
+                bg_color: None,
+                show_whitespace: false,
+                blink: None,
+                underline: None,
+                style: TextStyle::default(),
+                pasted: false,
+                hyperlink: None,
+            },
+            Action::Control(ctrl) => ctrl_to_dto(ctrl),
+            Action::DeviceControl(dcs) => dcs_to_dto(dcs, raw_bytes),
+            Action::OperatingSystemCommand(osc) => osc_to_dto(osc, raw_bytes),
+            Action::CSI(csi) => csi_to_dto(csi, sanitize_raw_bytes(raw_bytes)),
+            Action::Esc(e) => esc_to_dto(e, raw_bytes),
+            Action::Sixel(sixel) => match images::sixel_image(sixel) {
+                Some(image) => image_dto("Sixel", "Sixel image", image, raw_bytes),
+                None => VteEventDto::GenericEscape {
+                    title: Some("Sixel".into()),
+                    icon_svg: icon("image"),
+                    tooltip: Some("Sixel image".into()),
+                    raw_bytes: sanitize_raw_bytes(raw_bytes),
+                    cursor: None,
+                },
+            },
+            Action::XtGetTcap(x) => VteEventDto::GenericEscape {
+                title: Some("XTGETTCAP".into()),
+                icon_svg: None,
+                tooltip: Some(format!("Get termcap, terminfo for: {}", x.join(", "))),
+                raw_bytes: sanitize_raw_bytes(raw_bytes),
+                cursor: None,
+            },
+            Action::KittyImage(image) => match kitty_transmitted_image(image) {
+                Some(decoded) => image_dto("Kitty", &kitty_tooltip(image), decoded, raw_bytes),
+                None => VteEventDto::GenericEscape {
+                    title: Some("Kitty".into()),
+                    icon_svg: icon("image"),
+                    tooltip: Some(kitty_tooltip(image)),
+                    raw_bytes: sanitize_raw_bytes(raw_bytes),
+                    cursor: None,
+                },
+            },
+        }
+    }
+}
+
+fn image_dto(
+    title: &str,
+    description: &str,
+    image: images::InlineImage,
+    raw_bytes: &[u8],
+) -> VteEventDto {
+    VteEventDto::Image {
+        title: title.into(),
+        tooltip: format!("{description} ({}x{} pixels)", image.width, image.height),
+        data_url: image.data_url,
+        width: image.width,
+        height: image.height,
+        raw_bytes: sanitize_raw_bytes(raw_bytes),
+    }
+}
+
+/// The picture carried by a Kitty command that transmits image data, if we can decode it
+fn kitty_transmitted_image(image: &KittyImage) -> Option<images::InlineImage> {
+    match image {
+        KittyImage::TransmitData { transmit, .. }
+        | KittyImage::TransmitDataAndDisplay { transmit, .. } => {
+            // replies from the terminal reuse the payload for an error message
+            if kitty_reply(transmit).is_some() {
+                return None;
+            }
+            images::kitty_image(transmit)
+        }
+        _ => None,
+    }
+}
+
+/// Serialize a batch of DTOs for the websocket. In minimal mode inline SVGs are swapped for their
+/// icon keys and null fields are dropped, which makes a big difference over slow connections
+pub fn serialize_dtos<T: Serialize>(dtos: &[T], minimal: bool) -> String {
+    if !minimal {
+        return serde_json::to_string(dtos).unwrap();
+    }
+
+    let minimized: Vec<serde_json::Value> = dtos
+        .iter()
+        .map(|dto| {
+            let mut value = serde_json::to_value(dto).unwrap();
+            if let serde_json::Value::Object(fields) = &mut value {
+                if let Some(serde_json::Value::String(svg)) = fields.remove("icon_svg") {
+                    match icon_key(&svg) {
+                        Some(key) => fields.insert("icon".into(), key.into()),
+                        None => fields.insert("icon_svg".into(), svg.into()),
+                    };
+                }
+                fields.retain(|_, v| !v.is_null());
+            }
+            value
+        })
+        .collect();
+    serde_json::to_string(&minimized).unwrap()
+}
+
+fn osc_to_dto(osc: &OperatingSystemCommand, raw_bytes: &[u8]) -> VteEventDto {
+    let raw_bytes_str = sanitize_raw_bytes(raw_bytes);
+    match osc {
+        OperatingSystemCommand::SetHyperlink(link) => match link {
+            // OSC 8 ; id=x ; ST. Only an empty URI matters for ending a link, so the id is just informative
+            Some(link) if link.uri().is_empty() => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("hyperlink-off"),
+                tooltip: Some(match link.params().get("id") {
+                    Some(id) => format!("End hyperlink (id {id})"),
+                    None => "End hyperlink".into(),
+                }),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            Some(link) => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("hyperlink"),
+                tooltip: Some(format!("Set hyperlink: {link}")),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            None => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("hyperlink-off"),
+                tooltip: Some("End hyperlink".into()),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+        },
+        // OSC 0, 1 and 2, plus the Sun variants (OSC l and OSC L)
+        OperatingSystemCommand::SetIconNameAndWindowTitle(title)
+        | OperatingSystemCommand::SetWindowTitle(title)
+        | OperatingSystemCommand::SetWindowTitleSun(title)
+        | OperatingSystemCommand::SetIconName(title)
+        | OperatingSystemCommand::SetIconNameSun(title) => {
+            let what = match osc {
+                OperatingSystemCommand::SetIconNameAndWindowTitle(_) => {
+                    "window title and icon name"
+                }
+                OperatingSystemCommand::SetIconName(_)
+                | OperatingSystemCommand::SetIconNameSun(_) => "icon name",
+                _ => "window title",
+            };
+            VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("title"),
+                tooltip: Some(if title.is_empty() {
+                    format!("Clear {what}")
+                } else {
+                    format!("Set {what}: {title}")
+                }),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            }
+        }
+        // OSC 110-119
+        OperatingSystemCommand::ResetDynamicColor(color) => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("color-reset"),
+            tooltip: Some(format!("Reset {}", dynamic_color_name(*color))),
+            raw_bytes: raw_bytes_str,
+            cursor: None,
+        },
+        // OSC 10-19. A terminal's reply to a query looks exactly like setting the color
+        OperatingSystemCommand::ChangeDynamicColors(number, colors) if colors.len() == 1 => {
+            let name = dynamic_color_name(*number);
+            match &colors[0] {
+                ColorOrQuery::Query => VteEventDto::GenericEscape {
+                    title: Some("OSC".into()),
+                    icon_svg: None,
+                    tooltip: Some(format!("Query {name}")),
+                    raw_bytes: raw_bytes_str,
+                    cursor: None,
+                },
+                ColorOrQuery::Color(srgba) => {
+                    let hex = srgba.to_rgb_string();
+                    let tooltip = if *number == DynamicColorNumber::TextCursorColor {
+                        format!("Set cursor color (OSC 12): {hex}{CURSOR_APPEARANCE}")
+                    } else {
+                        format!(
+                            "The {name} is {hex} (set by the program, or reported by the terminal)"
+                        )
+                    };
+                    VteEventDto::ColorEscape {
+                        title: Some("Color".into()),
+                        icon_svg: None,
+                        tooltip: Some(tooltip),
+                        color: hex,
+                        raw_bytes: raw_bytes_str,
+                        cursor: None,
+                    }
+                }
+            }
+        }
+        // OSC 104
+        OperatingSystemCommand::ResetColors(indices) => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("color-reset"),
+            tooltip: Some(if indices.is_empty() {
+                "Reset entire palette (OSC 104)".into()
+            } else {
+                format!("Reset palette colors {} (OSC 104)", index_ranges(indices))
+            }),
+            raw_bytes: raw_bytes_str,
+            cursor: None,
+        },
+        // termwiz gives up on an OSC 8 without exactly two params, or with params that aren't key=value
+        OperatingSystemCommand::Unspecified(parts)
+            if parts.first().is_some_and(|first| first == b"8") =>
+        {
+            VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("hyperlink"),
+                tooltip: Some(
+                    "Malformed hyperlink (OSC 8): expected OSC 8 ; key=value:... ; URI ST".into(),
+                ),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            }
+        }
+        // OSC 105: xterm's colors for bold, underlined, blinking, reverse and italic text
+        OperatingSystemCommand::Unspecified(parts)
+            if parts.first().is_some_and(|first| first == b"105") =>
+        {
+            let colors: Vec<&str> = parts[1..]
+                .iter()
+                .map(|part| special_color_name(part))
+                .collect();
+            VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("color-reset"),
+                tooltip: Some(if colors.is_empty() {
+                    "Reset all special colors (OSC 105)".into()
+                } else {
+                    format!("Reset special colors: {} (OSC 105)", colors.join(", "))
+                }),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            }
+        }
+        // OSC 106: turn the special colors from OSC 5 on or off
+        OperatingSystemCommand::Unspecified(parts)
+            if parts.first().is_some_and(|first| first == b"106") =>
+        {
+            let changes: Vec<String> = parts[1..]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [color, setting] if setting == b"0" => {
+                        format!("disable {}", special_color_name(color))
+                    }
+                    [color, _] => format!("enable {}", special_color_name(color)),
+                    [color] => format!("{} (no setting given)", special_color_name(color)),
+                    _ => unreachable!(),
+                })
+                .collect();
+            VteEventDto::GenericEscape {
+                title: Some("OSC".into()),
+                icon_svg: None,
+                tooltip: Some(format!("Special colors: {} (OSC 106)", changes.join(", "))),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            }
+        }
+        // ConEmu/Windows Terminal taskbar progress: OSC 9;4;state;pct
+        OperatingSystemCommand::Unspecified(parts)
+            if parts.len() >= 2 && parts[0] == b"9" && parts[1] == b"4" =>
+        {
+            VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("progress"),
+                tooltip: Some(progress_tooltip(&parts[2..])),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            }
+        }
+        OperatingSystemCommand::FinalTermSemanticPrompt(FinalTermSemanticPrompt::FreshLine) => {
+            VteEventDto::GenericEscape {
+                title: Some("OSC 133".into()),
+                icon_svg: None,
+                tooltip: Some(
+                    "Fresh line: go to the start of the next line, unless already at the start of one"
+                        .into(),
+                ),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            }
+        }
+        OperatingSystemCommand::FinalTermSemanticPrompt(marker) => VteEventDto::PromptMarker {
+            kind: match marker {
+                FinalTermSemanticPrompt::FreshLineAndStartPrompt { .. }
+                | FinalTermSemanticPrompt::MarkEndOfCommandWithFreshLine { .. }
+                | FinalTermSemanticPrompt::StartPrompt(_)
+                | FinalTermSemanticPrompt::FreshLine => PromptMarkerKind::PromptStart,
+                FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker
+                | FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilEndOfLine => {
+                    PromptMarkerKind::CommandStart
+                }
+                FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { .. } => {
+                    PromptMarkerKind::OutputStart
+                }
+                FinalTermSemanticPrompt::CommandStatus { .. } => PromptMarkerKind::CommandFinished,
+            },
+            exit_code: match marker {
+                // termwiz reports a missing status as 0, so check that there was one
+                FinalTermSemanticPrompt::CommandStatus { status, .. }
+                    if raw_bytes.windows(6).any(|window| window == b"133;D;") =>
+                {
+                    Some(*status)
+                }
+                _ => None,
+            },
+            raw_bytes: raw_bytes_str,
+        },
+        _ => VteEventDto::GenericEscape {
+            title: Some("OSC".into()),
+            icon_svg: None,
+            tooltip: Some(format!("{osc:?}")),
+            raw_bytes: sanitize_raw_bytes(raw_bytes),
+            cursor: None,
+        },
+    }
+}
+
+/// Palette indices as a compact list, e.g. `1-3, 9`
+fn index_ranges(indices: &[u8]) -> String {
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut ranges: Vec<(u8, u8)> = vec![];
+    for index in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| match end - start {
+            0 => start.to_string(),
+            1 => format!("{start}, {end}"),
+            _ => format!("{start}-{end}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The colors that OSC 5/105/106 refer to by number
+fn special_color_name(number: &[u8]) -> &'static str {
+    match number {
+        b"0" => "bold",
+        b"1" => "underline",
+        b"2" => "blink",
+        b"3" => "reverse",
+        b"4" => "italic",
+        _ => "unknown special color",
+    }
+}
+
+fn dynamic_color_name(color: DynamicColorNumber) -> &'static str {
+    match color {
+        DynamicColorNumber::TextForegroundColor => "default foreground color",
+        DynamicColorNumber::TextBackgroundColor => "default background color",
+        DynamicColorNumber::TextCursorColor => "cursor color",
+        DynamicColorNumber::MouseForegroundColor => "mouse pointer foreground color",
+        DynamicColorNumber::MouseBackgroundColor => "mouse pointer background color",
+        DynamicColorNumber::TektronixForegroundColor => "Tektronix foreground color",
+        DynamicColorNumber::TektronixBackgroundColor => "Tektronix background color",
+        DynamicColorNumber::HighlightBackgroundColor => "highlight background color",
+        DynamicColorNumber::TektronixCursorColor => "Tektronix cursor color",
+        DynamicColorNumber::HighlightForegroundColor => "highlight foreground color",
+    }
+}
+
+fn progress_tooltip(params: &[Vec<u8>]) -> String {
+    let param = |i: usize| -> Option<u32> {
+        params
+            .get(i)
+            .and_then(|p| std::str::from_utf8(p).ok())
+            .and_then(|p| p.parse().ok())
+    };
+    let percent = param(1).unwrap_or(0).min(100);
+    match param(0).unwrap_or(0) {
+        0 => "Clear progress".into(),
+        1 => format!("Set taskbar progress: {percent}% (normal)"),
+        2 => format!("Set taskbar progress: {percent}% (error)"),
+        3 => "Indeterminate progress".into(),
+        4 => format!("Set taskbar progress: {percent}% (paused)"),
+        state => format!("Set taskbar progress: unknown state {state}"),
+    }
+}
+
+const SS2_TOOLTIP: &str = "SS2 / Single Shift 2: use G2 charset for the next character only";
+const SS3_TOOLTIP: &str = "SS3 / Single Shift 3: use G3 charset for the next character only";
+
+/// The locking shifts that termwiz doesn't know about. LS0 and LS1 are the SI and SO control codes
+fn locking_shift_tooltip(control: u8) -> Option<&'static str> {
+    match control {
+        b'n' => Some("LS2 / Locking Shift 2: invoke G2 charset into GL until further notice"),
+        b'o' => Some("LS3 / Locking Shift 3: invoke G3 charset into GL until further notice"),
+        b'~' => {
+            Some("LS1R / Locking Shift 1 Right: invoke G1 charset into GR until further notice")
+        }
+        b'}' => {
+            Some("LS2R / Locking Shift 2 Right: invoke G2 charset into GR until further notice")
+        }
+        b'|' => {
+            Some("LS3R / Locking Shift 3 Right: invoke G3 charset into GR until further notice")
+        }
+        _ => None,
+    }
+}
+
+/// Spell out the control fields of a Kitty graphics command, e.g. "Kitty: transmit RGBA image 100x100, id 5"
+fn kitty_tooltip(image: &KittyImage) -> String {
+    match image {
+        KittyImage::TransmitData { transmit, .. } => match kitty_reply(transmit) {
+            Some(reply) => format!("Kitty: {reply}"),
+            None => format!("Kitty: transmit {}", kitty_transmit(transmit)),
+        },
+        KittyImage::TransmitDataAndDisplay {
+            transmit,
+            placement,
+            ..
+        } => format!(
+            "Kitty: transmit and display {}{}",
+            kitty_transmit(transmit),
+            kitty_placement(placement)
+        ),
+        KittyImage::Display {
+            image_id,
+            image_number,
+            placement,
+            ..
+        } => format!(
+            "Kitty: display image{}{}",
+            kitty_ids(*image_id, *image_number),
+            kitty_placement(placement)
+        ),
+        KittyImage::Delete { what, .. } => format!("Kitty: {}", kitty_delete(what)),
+        KittyImage::Query { transmit } => format!(
+            "Kitty: query support for {} (the terminal replies if it understands Kitty graphics)",
+            kitty_transmit(transmit)
+        ),
+        KittyImage::TransmitFrame {
+            transmit, frame, ..
+        } => {
+            let mut desc = format!(
+                "Kitty: transmit animation frame for {}",
+                kitty_transmit(transmit)
+            );
+            if let Some(number) = frame.frame_number {
+                desc.push_str(&format!(", editing frame {number}"));
+            }
+            if let Some(duration) = frame.duration_ms {
+                desc.push_str(&format!(", shown for {duration}ms"));
+            }
+            desc
+        }
+        KittyImage::ComposeFrame { frame, .. } => {
+            let mut desc = format!(
+                "Kitty: compose animation frames of image{}",
+                kitty_ids(frame.image_id, frame.image_number)
+            );
+            if let (Some(source), Some(target)) = (frame.source_frame, frame.target_frame) {
+                desc.push_str(&format!(", frame {source} onto frame {target}"));
+            }
+            desc
+        }
+    }
+}
+
+/// The terminal's reply to a command uses the same APC syntax, with "OK" or an error code
+/// (`ENOENT:...`) where the payload would be. Base64 never contains a colon, so this can't be image data
+fn kitty_reply(transmit: &KittyImageTransmit) -> Option<String> {
+    let KittyImageData::Direct(payload) = &transmit.data else {
+        return None;
+    };
+    if transmit.format.is_some() || transmit.width.is_some() || transmit.height.is_some() {
+        return None;
+    }
+    let ids = kitty_ids(transmit.image_id, transmit.image_number);
+    if payload == "OK" {
+        Some(format!("terminal reply for image{ids}: OK"))
+    } else if payload.starts_with('E') && payload.contains(':') {
+        Some(format!("terminal reply for image{ids}: error {payload}"))
+    } else {
+        None
+    }
+}
+
+fn kitty_transmit(transmit: &KittyImageTransmit) -> String {
+    let mut desc = match transmit.format {
+        Some(KittyImageFormat::Rgb) => "RGB image".to_string(),
+        Some(KittyImageFormat::Png) => "PNG image".to_string(),
+        // RGBA is the default when the format isn't given
+        Some(KittyImageFormat::Rgba) | None => "RGBA image".to_string(),
+    };
+    if let (Some(width), Some(height)) = (transmit.width, transmit.height) {
+        desc.push_str(&format!(" {width}x{height}"));
+    }
+    let ids = kitty_ids(transmit.image_id, transmit.image_number);
+    if !ids.is_empty() {
+        desc.push(',');
+        desc.push_str(&ids);
+    }
+    match &transmit.data {
+        KittyImageData::Direct(_) | KittyImageData::DirectBin(_) => {}
+        KittyImageData::File { path, .. } => desc.push_str(&format!(", read from file {path}")),
+        KittyImageData::TemporaryFile { path, .. } => {
+            desc.push_str(&format!(", read from temporary file {path}"))
+        }
+        KittyImageData::SharedMem { name, .. } => {
+            desc.push_str(&format!(", read from shared memory {name}"))
+        }
+    }
+    if transmit.compression == KittyImageCompression::Deflate {
+        desc.push_str(", zlib compressed");
+    }
+    if transmit.more_data_follows {
+        desc.push_str(", more chunks follow");
+    }
+    desc
+}
+
+/// " id 5, number 2", or nothing if neither is given
+fn kitty_ids(image_id: Option<u32>, image_number: Option<u32>) -> String {
+    let ids: Vec<String> = [("id", image_id), ("number", image_number)]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{name} {value}")))
+        .collect();
+    if ids.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", ids.join(", "))
+    }
+}
+
+fn kitty_placement(placement: &KittyImagePlacement) -> String {
+    let mut desc = String::new();
+    if let Some(id) = placement.placement_id {
+        desc.push_str(&format!(", placement {id}"));
+    }
+    match (placement.columns, placement.rows) {
+        (Some(columns), Some(rows)) => desc.push_str(&format!(", {columns}x{rows} cells")),
+        (Some(columns), None) => desc.push_str(&format!(", {columns} columns wide")),
+        (None, Some(rows)) => desc.push_str(&format!(", {rows} rows tall")),
+        (None, None) => {}
+    }
+    if let Some(z) = placement.z_index {
+        desc.push_str(&format!(", z-index {z}"));
+    }
+    if placement.do_not_move_cursor {
+        desc.push_str(", cursor stays put");
+    }
+    desc
+}
+
+fn kitty_delete(what: &KittyImageDelete) -> String {
+    let (target, delete) = match what {
+        KittyImageDelete::All { delete } => ("all images on screen".to_string(), delete),
+        KittyImageDelete::ByImageId {
+            image_id,
+            placement_id,
+            delete,
+        } => (
+            match placement_id {
+                Some(placement) => format!("image id {image_id}, placement {placement}"),
+                None => format!("image id {image_id}"),
+            },
+            delete,
+        ),
+        KittyImageDelete::ByImageNumber {
+            image_number,
+            placement_id,
+            delete,
+        } => (
+            match placement_id {
+                Some(placement) => format!("image number {image_number}, placement {placement}"),
+                None => format!("image number {image_number}"),
+            },
+            delete,
+        ),
+        KittyImageDelete::AtCursorPosition { delete } => {
+            ("images at the cursor".to_string(), delete)
+        }
+        KittyImageDelete::AnimationFrames { delete } => ("animation frames".to_string(), delete),
+        KittyImageDelete::DeleteAt { x, y, delete } => {
+            (format!("images at cell ({x}, {y})"), delete)
+        }
+        KittyImageDelete::DeleteAtZ { x, y, z, delete } => (
+            format!("images at cell ({x}, {y}) with z-index {z}"),
+            delete,
+        ),
+        KittyImageDelete::DeleteColumn { x, delete } => (format!("images in column {x}"), delete),
+        KittyImageDelete::DeleteRow { y, delete } => (format!("images in row {y}"), delete),
+        KittyImageDelete::DeleteZ { z, delete } => (format!("images with z-index {z}"), delete),
+    };
+    if *delete {
+        format!("delete {target} and free the image data")
+    } else {
+        format!("delete {target} (the image data is kept)")
+    }
+}
+
+/// termwiz swallows PM and SOS strings, and APC strings that aren't Kitty graphics commands,
+/// so all we see is the ST that ends them. The string itself is still in the raw bytes though
+fn application_string_tooltip(raw_bytes: &[u8]) -> Option<(&'static str, String)> {
+    let (title, name, payload) = match raw_bytes {
+        [0x1b, b'_', payload @ ..] => ("APC", "Application Program Command", payload),
+        [0x1b, b'^', payload @ ..] => ("PM", "Privacy Message", payload),
+        [0x1b, b'X', payload @ ..] => ("SOS", "Start of String", payload),
+        _ => return None,
+    };
+    let payload = payload.strip_suffix(b"\x1b\\").unwrap_or(payload);
+    let mut tooltip = format!("{title} ({name}): {}", sanitize_raw_bytes(payload));
+    if title == "APC" && payload.starts_with(b"G") {
+        tooltip.push_str(" (looks like a Kitty graphics command, but it could not be parsed)");
+    }
+    Some((title, tooltip))
+}
+
+/// A DCS action on its own. `DtoBuilder` collects whole strings and describes them with
+/// `dcs_string_to_dto` instead, so this is for when there's nothing to go on but the one action
+fn dcs_to_dto(dcs: &DeviceControlMode, raw_bytes: &[u8]) -> VteEventDto {
+    let (title, tooltip) = match dcs {
+        DeviceControlMode::ShortDeviceControl(short) => {
+            match dcs_description(&short.params, &short.intermediates, short.byte, &short.data) {
+                Some((title, tooltip)) => (Some(title), tooltip),
+                None => (Some("DCS"), format!("{dcs:?}")),
+            }
+        }
+        DeviceControlMode::Enter(enter) => {
+            match dcs_description(&enter.params, &enter.intermediates, enter.byte, &[]) {
+                Some((title, tooltip)) => (Some(title), format!("{tooltip} (the string follows)")),
+                None => (Some("DCS"), format!("{dcs:?}")),
+            }
+        }
+        DeviceControlMode::Data(_) => (None, "DCS string data".into()),
+        DeviceControlMode::Exit => (None, "End of DCS string".into()),
+        _ => (Some("DCS"), format!("{dcs:?}")),
+    };
+    VteEventDto::GenericEscape {
+        title: title.map(Into::into),
+        icon_svg: None,
+        tooltip: Some(tooltip),
+        raw_bytes: sanitize_raw_bytes(raw_bytes),
+        cursor: None,
+    }
+}
+
+/// A whole DCS string, from its introducer to its ST
+fn dcs_string_to_dto(dcs: &PendingDcs) -> VteEventDto {
+    let enter = &dcs.enter;
+    let (title, tooltip) =
+        match dcs_description(&enter.params, &enter.intermediates, enter.byte, &dcs.data) {
+            Some((title, tooltip)) => (title, tooltip),
+            None => (
+                "DCS",
+                format!("{enter:?} with data {}", sanitize_raw_bytes(&dcs.data)),
+            ),
+        };
+    VteEventDto::GenericEscape {
+        title: Some(title.into()),
+        icon_svg: None,
+        tooltip: Some(tooltip),
+        raw_bytes: sanitize_raw_bytes(&dcs.raw_bytes),
+        cursor: None,
+    }
+}
+
+/// (title, tooltip) for the DCS strings we know, going by their intermediates and final byte
+fn dcs_description(
+    params: &[i64],
+    intermediates: &[u8],
+    byte: u8,
+    data: &[u8],
+) -> Option<(&'static str, String)> {
+    let text = String::from_utf8_lossy(data);
+    Some(match (intermediates, byte) {
+        (b"$", b'q') => (
+            "DECRQSS",
+            format!("Request status string: {}", status_string_name(&text)),
+        ),
+        // the setting's current value comes first, followed by the same letters the request used
+        (b"$", b'r') if params.first() == Some(&1) => {
+            let setting = text.trim_end_matches(|c: char| !c.is_ascii_digit() && c != ';');
+            let name = status_string_name(&text[setting.len()..]);
+            (
+                "DECRPSS",
+                format!("Status string report: {name} is {setting:?}"),
+            )
+        }
+        (b"$", b'r') => (
+            "DECRPSS",
+            "Status string report: the terminal didn't recognize the request".into(),
+        ),
+        (b"+", b'r') if params.first() == Some(&1) => (
+            "XTGETTCAP",
+            format!("Termcap/terminfo reply: {}", termcap_reply(&text)),
+        ),
+        (b"+", b'r') => (
+            "XTGETTCAP",
+            format!(
+                "Termcap/terminfo reply: the terminal doesn't know {}",
+                termcap_reply(&text)
+            ),
+        ),
+        (b">", b'|') => ("XTVERSION", format!("Terminal name and version: {text}")),
+        (b"!", b'|') => ("DECRPTUI", format!("Terminal unit ID: {text}")),
+        (b"=", b's') if params.first() == Some(&1) => (
+            "BSU",
+            "Begin synchronized update: the terminal holds off drawing until the update ends"
+                .into(),
+        ),
+        (b"=", b's') if params.first() == Some(&2) => (
+            "ESU",
+            "End synchronized update: the terminal draws everything since the update began".into(),
+        ),
+        // DCS tmux; ... ST, with `t` as the final byte
+        (b"", b't') if data.starts_with(b"mux;") => (
+            "tmux",
+            format!(
+                "tmux passthrough, sent on to the outer terminal: {}",
+                sanitize_raw_bytes(&data[4..])
+            ),
+        ),
+        (b"", b'p') if params == [1000] => ("tmux", "Enter tmux control mode".into()),
+        (b"", b'|') => (
+            "DECUDK",
+            "Define user-defined keys (DECUDK): program the function keys".into(),
+        ),
+        (b"", b'{') => ("DECDLD", "Download soft font (DECDLD)".into()),
+        _ => return None,
+    })
+}
+
+/// The setting a DECRQSS request or DECRPSS report is about, given the letters that identify it
+fn status_string_name(setting: &str) -> String {
+    let name = match setting {
+        "m" => "SGR (text attributes)",
+        " q" => "DECSCUSR (cursor style)",
+        "r" => "DECSTBM (top and bottom margins)",
+        "s" => "DECSLRM (left and right margins)",
+        "\"p" => "DECSCL (conformance level)",
+        "\"q" => "DECSCA (character protection)",
+        "t" => "DECSLPP (lines per page)",
+        "$|" => "DECSCPP (columns per page)",
+        "*|" => "DECSNLS (lines per screen)",
+        "$}" => "DECSASD (active status display)",
+        "$~" => "DECSSDT (status line type)",
+        other => return format!("{other:?}"),
+    };
+    name.into()
+}
+
+/// XTGETTCAP replies are `name=value` pairs separated by semicolons, with both halves hex encoded
+fn termcap_reply(text: &str) -> String {
+    let unhex = |hex: &str| -> String {
+        let bytes: Option<Vec<u8>> = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+            })
+            .collect();
+        match bytes {
+            Some(bytes) => sanitize_raw_bytes(&bytes),
+            None => hex.to_string(),
+        }
+    };
+    text.split(';')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => format!("{}={}", unhex(name), unhex(value)),
+            None => unhex(pair),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn esc_to_dto(esc: &Esc, raw_bytes: &[u8]) -> VteEventDto {
+    let raw_bytes_str = sanitize_raw_bytes(raw_bytes);
+    match esc {
+        Esc::Unspecified {
+            intermediate: None,
+            control,
+        } if locking_shift_tooltip(*control).is_some() => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("charset"),
+            tooltip: locking_shift_tooltip(*control).map(Into::into),
+            raw_bytes: raw_bytes_str,
+            cursor: None,
+        },
+        Esc::Unspecified {
+            intermediate: Some(b' '),
+            control: control @ (b'F' | b'G'),
+        } => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: None,
+            tooltip: Some(if *control == b'F' {
+                "Select 7-bit C1 control transmission (S7C1T): replies use ESC-prefixed controls"
+                    .into()
+            } else {
+                "Select 8-bit C1 control transmission (S8C1T): replies use single-byte controls"
+                    .into()
+            }),
+            raw_bytes: raw_bytes_str,
+            cursor: None,
+        },
+        Esc::Unspecified { .. } => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("unknown"),
+            tooltip: Some("Unspecified escape sequence".into()),
+            raw_bytes: raw_bytes_str,
+            cursor: None,
+        },
+        Esc::Code(code) => match code {
+            EscCode::StringTerminator if application_string_tooltip(raw_bytes).is_some() => {
+                let (title, tooltip) = application_string_tooltip(raw_bytes).unwrap();
+                VteEventDto::GenericEscape {
+                    title: Some(title.into()),
+                    icon_svg: None,
+                    tooltip: Some(tooltip),
+                    raw_bytes: raw_bytes_str,
+                    cursor: None,
+                }
+            }
+            EscCode::FullReset => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("reset"),
+                tooltip: Some(
+                    "Full reset (RIS): reset everything, including clearing the screen".into(),
+                ),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            EscCode::StringTerminator => VteEventDto::GenericEscape {
+                title: Some("\\".into()),
+                icon_svg: None,
+                tooltip: Some("ST / String Terminator".into()),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            EscCode::DecSaveCursorPosition => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("save-cursor"),
+                tooltip: Some("Save cursor position".into()),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            EscCode::DecRestoreCursorPosition => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("restore-cursor"),
+                tooltip: Some("Restore cursor position".into()),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            EscCode::SingleShiftG2 => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("charset"),
+                tooltip: Some(SS2_TOOLTIP.into()),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            EscCode::SingleShiftG3 => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("charset"),
+                tooltip: Some(SS3_TOOLTIP.into()),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            EscCode::AsciiCharacterSetG0
+            | EscCode::AsciiCharacterSetG1
+            | EscCode::UkCharacterSetG0
+            | EscCode::UkCharacterSetG1
+            | EscCode::DecLineDrawingG0
+            | EscCode::DecLineDrawingG1 => VteEventDto::GenericEscape {
+                title: None,
+                icon_svg: icon("charset"),
+                tooltip: Some(format!("{code:?}")),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+            _ => VteEventDto::GenericEscape {
+                title: Some("ESC".into()),
+                icon_svg: None,
+                tooltip: Some(format!("{code:?}")),
+                raw_bytes: raw_bytes_str,
+                cursor: None,
+            },
+        },
+    }
+}
+
+fn ctrl_to_dto(ctrl: &ControlCode) -> VteEventDto {
+    let as_byte = *ctrl as u8;
+    let raw_bytes = format!("{:#02x}", as_byte);
+
+    match ctrl {
+        ControlCode::Bell => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("bell"),
+            tooltip: Some("Bell".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::Backspace => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("backspace"),
+            tooltip: Some("Backspace".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::HorizontalTab => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("tab"),
+            tooltip: Some("Tab".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::LineFeed => VteEventDto::LineBreak { title: "LF".into() },
+        ControlCode::CarriageReturn => VteEventDto::LineBreak { title: "CR".into() },
+        ControlCode::Enquiry => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("answerback"),
+            // the reply comes back through stdin, which isn't captured, so only the request can be shown
+            tooltip: Some("Request answerback string (ENQ)".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::ShiftOut => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("charset"),
+            tooltip: Some("SO / Shift Out (LS1): invoke G1 charset into GL".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::ShiftIn => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("charset"),
+            tooltip: Some("SI / Shift In (LS0): invoke G0 charset into GL".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::SS2 => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("charset"),
+            tooltip: Some(SS2_TOOLTIP.into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::SS3 => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("charset"),
+            tooltip: Some(SS3_TOOLTIP.into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::DeviceControlOne => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("play"),
+            tooltip: Some("XON: resume transmission (flow control)".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::DeviceControlThree => VteEventDto::GenericEscape {
+            title: None,
+            icon_svg: icon("pause"),
+            tooltip: Some("XOFF: pause transmission (flow control)".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::Null => VteEventDto::GenericEscape {
+            title: Some("NUL".into()),
+            icon_svg: None,
+            tooltip: Some("NUL / Null (ignored by terminals, sometimes used as padding)".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::VerticalTab => VteEventDto::GenericEscape {
+            title: Some("VT".into()),
+            icon_svg: None,
+            tooltip: Some("VT / Vertical Tab (treated like a line feed by most terminals)".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::FormFeed => VteEventDto::GenericEscape {
+            title: Some("FF".into()),
+            icon_svg: None,
+            tooltip: Some("FF / Form Feed (treated like a line feed by most terminals)".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::Cancel => VteEventDto::GenericEscape {
+            title: Some("CAN".into()),
+            icon_svg: None,
+            tooltip: Some("CAN / Cancel: abort the escape sequence in progress".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::Substitute => VteEventDto::GenericEscape {
+            title: Some("SUB".into()),
+            icon_svg: None,
+            tooltip: Some("SUB / Substitute: abort the escape sequence in progress".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        ControlCode::Escape => VteEventDto::GenericEscape {
+            title: Some("ESC".into()),
+            icon_svg: None,
+            tooltip: Some("ESC / Escape that did not start a valid sequence".into()),
+            raw_bytes,
+            cursor: None,
+        },
+        _ => VteEventDto::GenericEscape {
+            title: Some(format!("{ctrl:?}")),
+            icon_svg: None,
+            tooltip: None,
+            raw_bytes,
+            cursor: None,
+        },
+    }
+}
+
+/// termwiz normalizes the different 256-color/truecolor syntaxes, but terminals don't all
+/// support the colon-separated one so it's worth pointing out which was used
+fn color_syntax(raw_bytes: &str, introducer: &str) -> String {
+    let Some(params) = raw_bytes
+        .strip_prefix(r"\x1b[")
+        .and_then(|params| params.strip_suffix('m'))
+    else {
+        return String::new();
+    };
+    for param in params.split(';') {
+        if param == introducer {
+            return format!(" (semicolon-separated {introducer};… syntax)");
+        }
+        if param.starts_with(&format!("{introducer}:")) {
+            return format!(
+                " (colon-separated {introducer}:… syntax, which not all terminals support)"
+            );
+        }
+    }
+    String::new()
+}
+
+fn csi_to_dto(csi: &CSI, raw_bytes: String) -> VteEventDto {
+    let (title, tooltip, icon_svg) = match csi {
+        CSI::Sgr(sgr) => match sgr {
+            Sgr::Reset => (
+                None,
+                Some("SGR (Select Graphic Rendition) Reset (reset all styles)".into()),
+                icon("reset"),
+            ),
+            Sgr::Blink(blink) => (
+                Some("Blink".into()),
+                Some(
+                    match blink {
+                        Blink::Slow => "Slow blink",
+                        Blink::Rapid => "Rapid blink",
+                        Blink::None => "Blink off",
+                    }
+                    .into(),
+                ),
+                None,
+            ),
+            Sgr::Foreground(color) => {
+                return VteEventDto::ColorEscape {
+                    title: Some("FG".into()),
+                    icon_svg: None,
+                    tooltip: Some(format!(
+                        "Set foreground color to: {color:?}{}",
+                        color_syntax(&raw_bytes, "38")
+                    )),
+                    color: hex_color(color).unwrap_or("black".into()),
+                    raw_bytes,
+                    cursor: None,
+                }
+            }
+            Sgr::Background(color) => {
+                return VteEventDto::ColorEscape {
+                    title: Some("BG".into()),
+                    icon_svg: None,
+                    tooltip: Some(format!(
+                        "Set background color to: {color:?}{}",
+                        color_syntax(&raw_bytes, "48")
+                    )),
+                    color: hex_color(color).unwrap_or("black".into()),
+                    raw_bytes,
+                    cursor: None,
+                }
+            }
+            // SGR 59
+            Sgr::UnderlineColor(ColorSpec::Default) => (
+                None,
+                Some("Reset underline color to default".into()),
+                icon("reset"),
+            ),
+            // SGR 4, 21 and 24, plus the 4:n subparameters for the newer styles
+            Sgr::Underline(underline) => (
+                Some("SGR".into()),
+                Some(
+                    match underline {
+                        Underline::None => "Underline off",
+                        Underline::Single => "Single underline",
+                        Underline::Double => "Double underline",
+                        Underline::Curly => "Curly underline",
+                        Underline::Dotted => "Dotted underline",
+                        Underline::Dashed => "Dashed underline",
+                    }
+                    .into(),
+                ),
+                None,
+            ),
+            Sgr::Overline(true) => (Some("SGR".into()), Some("Overline".into()), None),
+            Sgr::Overline(false) => (
+                Some("SGR".into()),
+                Some("Not overlined (SGR 55)".into()),
+                None,
+            ),
+            _ => (Some("SGR".into()), Some(format!("Set {sgr:?}")), None),
+        },
+        CSI::Cursor(cursor) => match cursor {
+            // a bare CSI I is also what terminals send when they gain focus (see CSI O in unspecified_csi_to_dto)
+            Cursor::ForwardTabulation(1) if raw_bytes == r"\x1b[I" => (
+                None,
+                Some(
+                    "Terminal gained focus (with focus reporting, private mode 1004, on). If a program wrote it, cursor forward 1 tab stop (CHT)"
+                        .into(),
+                ),
+                icon("focus"),
+            ),
+            // CHT and CBT
+            Cursor::ForwardTabulation(n) => (
+                None,
+                Some(format!("Cursor forward {n} {}", tab_stops(*n))),
+                icon("tab"),
+            ),
+            // DSR 6 and its reply. See DECXCPR in unspecified_csi_to_dto for the variant that includes the page
+            Cursor::RequestActivePositionReport => (
+                None,
+                Some("Request cursor position report (DSR 6, reply has no page number)".into()),
+                icon("cursor"),
+            ),
+            Cursor::ActivePositionReport { line, col } => (
+                None,
+                Some(format!(
+                    "Cursor position report (CPR): row {}, column {}",
+                    line.as_one_based(),
+                    col.as_one_based()
+                )),
+                icon("cursor"),
+            ),
+            Cursor::SetTopAndBottomMargins { top, bottom } => (
+                None,
+                Some(
+                    if top.as_one_based() <= 1 && bottom.as_one_based() == u32::MAX {
+                        "Reset scroll region to the whole screen (DECSTBM)".into()
+                    } else {
+                        format!(
+                            "Set scroll region to {} (DECSTBM)",
+                            scroll_region_rows(top.as_one_based(), bottom.as_one_based())
+                        )
+                    },
+                ),
+                icon("cursor"),
+            ),
+            Cursor::BackwardTabulation(n) => (
+                None,
+                Some(format!("Cursor backward {n} {}", tab_stops(*n))),
+                icon("tab"),
+            ),
+            // SCOSC and SCORC, the ANSI.SYS equivalents of DECSC and DECRC
+            Cursor::SaveCursor => (
+                None,
+                Some("Save cursor position (SCOSC)".into()),
+                icon("save-cursor"),
+            ),
+            Cursor::RestoreCursor => (
+                None,
+                Some("Restore cursor position (SCORC)".into()),
+                icon("restore-cursor"),
+            ),
+            Cursor::CursorStyle(style) => (
+                None,
+                Some(format!(
+                    "Set cursor shape (DECSCUSR): {}{CURSOR_APPEARANCE}",
+                    cursor_style_name(style)
+                )),
+                icon("cursor"),
+            ),
+            Cursor::LineTabulation(n) => (
+                None,
+                Some(format!("Cursor down {n} line {} (CVT)", tab_stops(*n))),
+                icon("tab"),
+            ),
+            Cursor::TabulationClear(clear) => (
+                None,
+                Some(format!("{} (TBC)", tabulation_clear_description(clear))),
+                icon("tab"),
+            ),
+            Cursor::TabulationControl(control) => (
+                None,
+                Some(format!("{} (CTC)", tabulation_control_description(control))),
+                icon("tab"),
+            ),
+            Cursor::SetLeftAndRightMargins { left, right } => (
+                None,
+                Some(if right.as_one_based() == u32::MAX {
+                    format!(
+                        "Set left and right margins to columns {} to the right edge (DECSLRM)",
+                        left.as_one_based()
+                    )
+                } else {
+                    format!(
+                        "Set left and right margins to columns {}–{} (DECSLRM)",
+                        left.as_one_based(),
+                        right.as_one_based()
+                    )
+                }),
+                icon("cursor"),
+            ),
+            _ => (None, Some(cursor_move_description(cursor)), icon("cursor")),
+        },
+        CSI::Edit(edit) => match edit {
+            Edit::EraseInLine(erase) => (
+                None,
+                Some(match erase {
+                    EraseInLine::EraseToEndOfLine => "Erase to end of line".into(),
+                    EraseInLine::EraseToStartOfLine => "Erase to start of line".into(),
+                    EraseInLine::EraseLine => "Erase line".into(),
+                }),
+                icon("erase"),
+            ),
+            Edit::EraseInDisplay(erase) => (
+                None,
+                Some(match erase {
+                    EraseInDisplay::EraseToEndOfDisplay => "Erase to end of display".into(),
+                    EraseInDisplay::EraseToStartOfDisplay => "Erase to start of display".into(),
+                    EraseInDisplay::EraseDisplay => "Erase display".into(),
+                    EraseInDisplay::EraseScrollback => "Erase scrollback".into(),
+                }),
+                icon("erase"),
+            ),
+            _ => (Some("Edit".into()), Some(format!("{edit:?}")), None),
+        },
+        // CSI::Edit(_) => todo!(),
+        CSI::Device(device) if matches!(**device, Device::SoftReset) => (
+            None,
+            Some("Soft reset (DECSTR): reset modes, margins, styles and the saved cursor, but not the screen".into()),
+            icon("reset"),
+        ),
+        CSI::Mouse(report) => (None, Some(mouse_description(report)), icon("mouse")),
+        CSI::Window(window) => (None, Some(window_description(window)), icon("window")),
+        // CSI::Keyboard(_) => todo!(),
+        // CSI::SelectCharacterPath(_, _) => todo!(),
+        CSI::Mode(mode_change) => match (bell_mode_tooltip(mode_change), mode_change) {
+            (Some(tooltip), _) => (None, Some(tooltip), icon("bell")),
+            (
+                None,
+                Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                    DecPrivateModeCode::StartBlinkingCursor,
+                )),
+            ) => (
+                None,
+                Some(format!(
+                    "Start blinking cursor (mode 12){CURSOR_APPEARANCE}"
+                )),
+                icon("cursor"),
+            ),
+            (
+                None,
+                Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                    DecPrivateModeCode::StartBlinkingCursor,
+                )),
+            ) => (
+                None,
+                Some(format!("Stop blinking cursor (mode 12){CURSOR_APPEARANCE}")),
+                icon("cursor"),
+            ),
+            (None, Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::OriginMode))) => (
+                None,
+                Some("Enable origin mode (DECOM): cursor positions are now relative to the scroll region".into()),
+                icon("cursor"),
+            ),
+            (
+                None,
+                Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::OriginMode)),
+            ) => (
+                None,
+                Some("Disable origin mode (DECOM): cursor positions are relative to the whole screen again".into()),
+                icon("cursor"),
+            ),
+            // DECCOLM. xterm ignores it unless 132-column switching is allowed (mode 40)
+            (
+                None,
+                Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                    DecPrivateModeCode::Select132Columns,
+                )),
+            ) => (
+                Some("132 columns".into()),
+                Some(format!("Switch to 132-column mode (DECCOLM){DECCOLM_EFFECTS}")),
+                None,
+            ),
+            (
+                None,
+                Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                    DecPrivateModeCode::Select132Columns,
+                )),
+            ) => (
+                Some("80 columns".into()),
+                Some(format!("Switch to 80-column mode (DECCOLM){DECCOLM_EFFECTS}")),
+                None,
+            ),
+            (None, Mode::XtermKeyMode { resource, value }) => {
+                let number = match resource {
+                    XtermKeyModifierResource::Keyboard => 0,
+                    XtermKeyModifierResource::CursorKeys => 1,
+                    XtermKeyModifierResource::FunctionKeys => 2,
+                    XtermKeyModifierResource::OtherKeys => 4,
+                };
+                (None, Some(modify_keys_tooltip(number, *value)), icon("keyboard"))
+            }
+            (None, Mode::QueryDecPrivateMode(mode)) => {
+                let number = dec_private_mode_number(mode);
+                (
+                    None,
+                    Some(format!(
+                        "Query whether {} is set (DECRQM)",
+                        mode_description(true, number)
+                    )),
+                    None,
+                )
+            }
+            (None, Mode::QueryMode(mode)) => {
+                let number = match mode {
+                    TerminalMode::Code(code) => code.clone() as i64,
+                    TerminalMode::Unspecified(number) => *number as i64,
+                };
+                (
+                    None,
+                    Some(format!(
+                        "Query whether {} is set (DECRQM)",
+                        mode_description(false, number)
+                    )),
+                    None,
+                )
+            }
+            (None, Mode::SetDecPrivateMode(mode) | Mode::ResetDecPrivateMode(mode)) => {
+                let set = matches!(mode_change, Mode::SetDecPrivateMode(_));
+                let number = dec_private_mode_number(mode);
+                let icon_key = match number {
+                    1004 => "focus",
+                    2004 => "paste",
+                    _ if set => "mode-on",
+                    _ => "mode-off",
+                };
+                (
+                    None,
+                    Some(mode_change_tooltip(true, set, number)),
+                    icon(icon_key),
+                )
+            }
+            (None, Mode::SetMode(mode) | Mode::ResetMode(mode)) => {
+                let set = matches!(mode_change, Mode::SetMode(_));
+                let number = match mode {
+                    TerminalMode::Code(code) => code.clone() as i64,
+                    TerminalMode::Unspecified(number) => *number as i64,
+                };
+                (
+                    None,
+                    Some(mode_change_tooltip(false, set, number)),
+                    icon(if set { "mode-on" } else { "mode-off" }),
+                )
+            }
+            // XTSAVE and XTRESTORE
+            (None, Mode::SaveDecPrivateMode(mode)) => (
+                None,
+                Some(format!(
+                    "Save whether {} is set (XTSAVE)",
+                    mode_description(true, dec_private_mode_number(mode))
+                )),
+                None,
+            ),
+            (None, Mode::RestoreDecPrivateMode(mode)) => (
+                None,
+                Some(format!(
+                    "Restore {} to how XTSAVE saved it (XTRESTORE)",
+                    mode_description(true, dec_private_mode_number(mode))
+                )),
+                None,
+            ),
+        },
+        CSI::Unspecified(unspecified) => return unspecified_csi_to_dto(unspecified, raw_bytes),
+        _ => (Some("CSI".into()), Some(format!("{csi:?}")), None),
+    };
+
+    VteEventDto::GenericEscape {
+        title,
+        tooltip,
+        icon_svg,
+        raw_bytes,
+        cursor: None,
+    }
+}
+
+/// e.g. "rows 2–23"
+fn scroll_region_rows(top: u32, bottom: u32) -> String {
+    if bottom == u32::MAX {
+        format!("rows {top} to the bottom of the screen")
+    } else {
+        format!("rows {top}–{bottom}")
+    }
+}
+
+/// The cursor's look is split across several sequences, which is confusing enough to point out each time
+const CURSOR_APPEARANCE: &str =
+    ". See also: OSC 12 (color), DECSCUSR (shape and blinking), mode 12 (blinking)";
+
+fn cursor_style_name(style: &CursorStyle) -> &'static str {
+    match style {
+        CursorStyle::Default => "terminal's default",
+        CursorStyle::BlinkingBlock => "blinking block",
+        CursorStyle::SteadyBlock => "steady block",
+        CursorStyle::BlinkingUnderline => "blinking underline",
+        CursorStyle::SteadyUnderline => "steady underline",
+        CursorStyle::BlinkingBar => "blinking bar",
+        CursorStyle::SteadyBar => "steady bar",
+    }
+}
+
+/// xterm's DEC private modes that configure how the bell (BEL) is presented. There's no widely supported
+/// mode for a visual bell; terminals that have one configure it outside of escape sequences
+fn bell_mode_tooltip(mode: &Mode) -> Option<String> {
+    let (action, code) = match mode {
+        Mode::SetDecPrivateMode(DecPrivateMode::Unspecified(code)) => ("Enable", code),
+        Mode::ResetDecPrivateMode(DecPrivateMode::Unspecified(code)) => ("Disable", code),
+        _ => return None,
+    };
+    let setting = match code {
+        44 => "margin bell",
+        1042 => "urgency window hint on bell",
+        1043 => "raising the window on bell",
+        _ => return None,
+    };
+    Some(format!("{action} {setting}"))
+}
+
+fn dec_private_mode_number(mode: &DecPrivateMode) -> i64 {
+    match mode {
+        DecPrivateMode::Code(code) => code.clone() as i64,
+        DecPrivateMode::Unspecified(number) => *number as i64,
+    }
+}
+
+/// e.g. "private mode 2026 (synchronized output)". DEC private modes (`CSI ? ... h`) and ANSI modes
+/// (`CSI ... h`) are numbered separately
+fn mode_description(private: bool, number: i64) -> String {
+    let kind = if private { "private mode" } else { "mode" };
+    match mode_name(private, number) {
+        Some(name) => format!("{kind} {number} ({name})"),
+        None => format!("{kind} {number}"),
+    }
+}
+
+/// DECSET/DECRST (`CSI ? Pm h`/`l`) and SM/RM (`CSI Pm h`/`l`), e.g. "Enable bracketed paste (private mode 2004)"
+fn mode_change_tooltip(private: bool, set: bool, number: i64) -> String {
+    let kind = if private { "private mode" } else { "mode" };
+    if private && number == 25 {
+        let verb = if set { "Show" } else { "Hide" };
+        return format!("{verb} cursor ({kind} 25)");
+    }
+    let verb = if set { "Enable" } else { "Disable" };
+    // these two are behind a lot of confusing input, so say what they do
+    let effect = match (private && set, number) {
+        (true, 1004) => {
+            ": the terminal sends ESC [ I when it gains focus and ESC [ O when it loses it"
+        }
+        (true, 2004) => ": the terminal wraps pasted text in ESC [ 200~ and ESC [ 201~",
+        _ => "",
+    };
+    match mode_name(private, number) {
+        Some(name) => format!("{verb} {name} ({kind} {number}){effect}"),
+        None => format!("{verb} {kind} {number} (unknown)"),
+    }
+}
+
+fn mode_name(private: bool, number: i64) -> Option<&'static str> {
+    if private {
+        match number {
+            1 => Some("application cursor keys"),
+            3 => Some("132 column mode"),
+            5 => Some("reverse video"),
+            6 => Some("origin mode"),
+            7 => Some("autowrap"),
+            8 => Some("auto-repeat keys"),
+            9 => Some("X10 mouse reporting"),
+            12 => Some("blinking cursor"),
+            25 => Some("cursor visibility"),
+            44 => Some("margin bell"),
+            47 | 1047 => Some("alternate screen"),
+            1000 => Some("mouse click reporting"),
+            1002 => Some("mouse drag reporting"),
+            1003 => Some("all mouse motion reporting"),
+            1004 => Some("focus reporting"),
+            1005 => Some("UTF-8 mouse encoding"),
+            1006 => Some("SGR mouse encoding"),
+            1015 => Some("urxvt mouse encoding"),
+            1016 => Some("SGR pixel mouse encoding"),
+            1042 => Some("urgency window hint on bell"),
+            1043 => Some("raising the window on bell"),
+            1048 => Some("saved cursor"),
+            1049 => Some("alternate screen with saved cursor"),
+            2004 => Some("bracketed paste"),
+            2026 => Some("synchronized output"),
+            2027 => Some("grapheme clustering"),
+            2048 => Some("in-band resize notifications"),
+            _ => None,
+        }
+    } else {
+        match number {
+            2 => Some("keyboard lock"),
+            4 => Some("insert mode"),
+            12 => Some("send/receive mode (no local echo)"),
+            20 => Some("automatic newline"),
+            _ => None,
+        }
+    }
+}
+
+/// Changing the width is never just a resize
+const DECCOLM_EFFECTS: &str =
+    ": resizes the screen, clears it, resets the scroll region and moves the cursor home";
+
+/// xterm's resources for how keys with modifiers are encoded, by the number XTMODKEYS uses for them
+fn key_modifier_resource(number: i64) -> String {
+    match number {
+        0 => "modifyKeyboard".into(),
+        1 => "modifyCursorKeys".into(),
+        2 => "modifyFunctionKeys".into(),
+        3 => "modifyKeypadKeys".into(),
+        4 => "modifyOtherKeys".into(),
+        6 => "modifyModifierKeys".into(),
+        7 => "modifySpecialKeys".into(),
+        _ => format!("unknown key modifier resource {number}"),
+    }
+}
+
+/// XTMODKEYS (`CSI > Pp ; Pv m`). Leaving out the value resets the resource to its default
+fn modify_keys_tooltip(resource: i64, value: Option<i64>) -> String {
+    let name = key_modifier_resource(resource);
+    let Some(value) = value else {
+        return format!("Reset {name} to default (XTMODKEYS)");
+    };
+    let meaning = match (resource, value) {
+        (4, 0) => ": report modified keys the traditional way",
+        (4, 1) => {
+            ": report modified keys as escape sequences, except ones with well-known behavior"
+        }
+        (4, 2) => ": report all modified keys as escape sequences",
+        _ => "",
+    };
+    format!("Set {name} to {value} (XTMODKEYS){meaning}")
+}
+
+/// DECRPM, where the terminal says whether a mode queried with DECRQM is set
+fn mode_report_tooltip(private: bool, mode: i64, status: i64) -> String {
+    let mode = mode_description(private, mode);
+    match status {
+        0 => format!("Terminal doesn't recognize {mode} (DECRPM)"),
+        1 => format!("Terminal reports {mode} is set (DECRPM)"),
+        2 => format!("Terminal reports {mode} is reset (DECRPM)"),
+        3 => format!("Terminal reports {mode} is permanently set (DECRPM)"),
+        4 => format!("Terminal reports {mode} is permanently reset (DECRPM)"),
+        _ => format!("Terminal reports unknown status {status} for {mode} (DECRPM)"),
+    }
+}
+
+/// `control` is `t` for DECSWBV (the warning bell, i.e. BEL) or `u` for DECSMBV (the margin bell)
+fn bell_volume_tooltip(control: char, volume: i64) -> String {
+    let (bell, abbreviation) = if control == 't' {
+        ("warning", "DECSWBV")
+    } else {
+        ("margin", "DECSMBV")
+    };
+    let level = match (control, volume) {
+        (_, 1) | ('t', 0) => "off",
+        (_, 2..=4) => "low",
+        (_, 0 | 5..=8) => "high",
+        _ => "unknown",
+    };
+    format!("Set {bell} bell volume: {level} ({abbreviation})")
+}
+
+/// DECSCL picks which VT model to behave like. Levels above VT100 also pick how C1 controls get sent:
+/// 1 means 7-bit, 0 or 2 (or nothing) means 8-bit
+fn conformance_level_tooltip(level: i64, controls: Option<i64>) -> String {
+    let level = match level {
+        61 => "VT100",
+        62 => "VT200",
+        63 => "VT300",
+        64 => "VT400",
+        65 => "VT500",
+        _ => return format!("Set conformance level to unknown level {level} (DECSCL)"),
+    };
+    let controls = match (level, controls) {
+        ("VT100", _) | (_, Some(1)) => "7-bit controls",
+        (_, None | Some(0 | 2)) => "8-bit controls",
+        _ => "unknown control mode",
+    };
+    format!("Set conformance level to {level}, {controls} (DECSCL)")
+}
+
+fn title_modes_tooltip(action: &str, modes: &[CsiParam]) -> String {
+    let modes: Vec<&str> = modes
+        .iter()
+        .filter_map(|param| match param {
+            CsiParam::Integer(0) => Some("set window/icon labels using hexadecimal"),
+            CsiParam::Integer(1) => Some("query window/icon labels using hexadecimal"),
+            CsiParam::Integer(2) => Some("set window/icon labels using UTF-8"),
+            CsiParam::Integer(3) => Some("query window/icon labels using UTF-8"),
+            CsiParam::Integer(_) => Some("unknown mode"),
+            _ => None,
+        })
+        .collect();
+    if modes.is_empty() {
+        format!("{action} title modes (none specified)")
+    } else {
+        format!("{action} title modes: {}", modes.join(", "))
+    }
+}
+
+/// XTWINOPS (CSI t) and friends. Most terminals ignore the ones that move or resize the window itself
+/// unless they're explicitly allowed to
+fn window_description(window: &Window) -> String {
+    let size = |what: &str, height: &Option<i64>, width: &Option<i64>, unit: &str| {
+        // a missing or 0 size means "leave that dimension as it is"
+        let dimension = |n: &Option<i64>, name: &str| match n {
+            Some(n) if *n > 0 => format!("{n} {name}"),
+            _ => format!("current {name}"),
+        };
+        format!(
+            "{what} {} × {}",
+            dimension(height, &format!("{unit} high")),
+            dimension(width, &format!("{unit} wide"))
+        )
+    };
+    match window {
+        Window::DeIconify => "Restore window from minimized (XTWINOPS)".into(),
+        Window::Iconify => "Minimize window (XTWINOPS)".into(),
+        Window::MoveWindow { x, y } => format!("Move window to x={x}, y={y} pixels (XTWINOPS)"),
+        Window::ResizeWindowPixels { width, height } => {
+            format!(
+                "{} (XTWINOPS)",
+                size("Resize window to", height, width, "pixels")
+            )
+        }
+        Window::ResizeWindowCells { width, height } => match (height, width) {
+            (Some(rows), Some(cols)) if *rows > 0 && *cols > 0 => {
+                format!("Resize terminal to {rows} rows × {cols} cols (XTWINOPS)")
+            }
+            _ => format!(
+                "{} (XTWINOPS)",
+                size("Resize terminal to", height, width, "cells")
+            ),
+        },
+        Window::RaiseWindow => "Raise window to the front (XTWINOPS)".into(),
+        Window::LowerWindow => "Lower window to the back (XTWINOPS)".into(),
+        Window::RefreshWindow => "Refresh window (XTWINOPS)".into(),
+        Window::RestoreMaximizedWindow => "Restore window from maximized (XTWINOPS)".into(),
+        Window::MaximizeWindow => "Maximize window (XTWINOPS)".into(),
+        Window::MaximizeWindowVertically => "Maximize window vertically (XTWINOPS)".into(),
+        Window::MaximizeWindowHorizontally => "Maximize window horizontally (XTWINOPS)".into(),
+        Window::UndoFullScreenMode => "Leave full screen (XTWINOPS)".into(),
+        Window::ChangeToFullScreenMode => "Go full screen (XTWINOPS)".into(),
+        Window::ToggleFullScreen => "Toggle full screen (XTWINOPS)".into(),
+        Window::ReportWindowState => "Report whether the window is minimized (XTWINOPS)".into(),
+        Window::ReportWindowPosition => "Report window position (XTWINOPS)".into(),
+        Window::ReportTextAreaPosition => "Report text area position (XTWINOPS)".into(),
+        Window::ReportTextAreaSizePixels => "Report text area size in pixels (XTWINOPS)".into(),
+        Window::ReportWindowSizePixels => "Report window size in pixels (XTWINOPS)".into(),
+        Window::ReportScreenSizePixels => "Report screen size in pixels (XTWINOPS)".into(),
+        Window::ReportCellSizePixels => "Report cell size in pixels (XTWINOPS)".into(),
+        Window::ReportCellSizePixelsResponse { width, height } => format!(
+            "Cell size report: {} (reply to XTWINOPS 16)",
+            size("cells are", height, width, "pixels")
+        ),
+        Window::ReportTextAreaSizeCells => {
+            "Report terminal size in rows and columns (XTWINOPS)".into()
+        }
+        Window::ReportScreenSizeCells => "Report screen size in rows and columns (XTWINOPS)".into(),
+        Window::ReportIconLabel => "Report icon label (XTWINOPS)".into(),
+        Window::ReportWindowTitle => "Report window title (XTWINOPS)".into(),
+        Window::PushIconAndWindowTitle => "Push icon and window title onto stack (XTWINOPS)".into(),
+        Window::PushIconTitle => "Push icon title onto stack (XTWINOPS)".into(),
+        Window::PushWindowTitle => "Push window title onto stack (XTWINOPS)".into(),
+        Window::PopIconAndWindowTitle => "Pop icon and window title from stack (XTWINOPS)".into(),
+        Window::PopIconTitle => "Pop icon title from stack (XTWINOPS)".into(),
+        Window::PopWindowTitle => "Pop window title from stack (XTWINOPS)".into(),
+        // anything termwiz adds later still shows its parameters
+        other => format!("Window operation: {other:?}"),
+    }
+}
+
+/// e.g. "Mouse: left button press at row 12, col 40". Terminals send these to programs that turn on mouse
+/// tracking, so they mostly show up in captured input
+fn mouse_description(report: &MouseReport) -> String {
+    let (button, position, modifiers) = match report {
+        MouseReport::SGR1006 {
+            x,
+            y,
+            button,
+            modifiers,
+        } => (button, format!("row {y}, col {x}"), modifiers),
+        MouseReport::SGR1016 {
+            x_pixels,
+            y_pixels,
+            button,
+            modifiers,
+        } => (button, format!("{x_pixels}px, {y_pixels}px"), modifiers),
+    };
+    let event = match button {
+        MouseButton::Button1Press => "left button press",
+        MouseButton::Button2Press => "middle button press",
+        MouseButton::Button3Press => "right button press",
+        MouseButton::Button4Press => "wheel up",
+        MouseButton::Button5Press => "wheel down",
+        MouseButton::Button6Press => "wheel left",
+        MouseButton::Button7Press => "wheel right",
+        MouseButton::Button1Release => "left button release",
+        MouseButton::Button2Release => "middle button release",
+        MouseButton::Button3Release => "right button release",
+        MouseButton::Button4Release => "button 4 release",
+        MouseButton::Button5Release => "button 5 release",
+        MouseButton::Button6Release => "button 6 release",
+        MouseButton::Button7Release => "button 7 release",
+        MouseButton::Button1Drag => "left button drag",
+        MouseButton::Button2Drag => "middle button drag",
+        MouseButton::Button3Drag => "right button drag",
+        MouseButton::None => "move",
+    };
+    let held: Vec<&str> = [
+        (Modifiers::SHIFT, "Shift"),
+        (Modifiers::ALT, "Alt"),
+        (Modifiers::CTRL, "Ctrl"),
+    ]
+    .into_iter()
+    .filter(|(modifier, _)| modifiers.contains(*modifier))
+    .map(|(_, name)| name)
+    .collect();
+    if held.is_empty() {
+        format!("Mouse: {event} at {position}")
+    } else {
+        format!("Mouse: {event} with {} at {position}", held.join("+"))
+    }
+}
+
+/// CUU, CUP and friends, e.g. "Move cursor up 3 rows (CUU)". Absolute positions are 1-based, like the
+/// parameters
+fn cursor_move_description(cursor: &Cursor) -> String {
+    match cursor {
+        Cursor::Up(n) => format!("Move cursor up {} (CUU)", rows(*n)),
+        Cursor::Down(n) => format!("Move cursor down {} (CUD)", rows(*n)),
+        Cursor::Right(n) => format!("Move cursor right {} (CUF)", columns(*n)),
+        Cursor::Left(n) => format!("Move cursor left {} (CUB)", columns(*n)),
+        Cursor::LinePositionForward(n) => format!("Move cursor down {} (VPR)", rows(*n)),
+        Cursor::LinePositionBackward(n) => format!("Move cursor up {} (VPB)", rows(*n)),
+        Cursor::CharacterPositionForward(n) => format!("Move cursor right {} (HPR)", columns(*n)),
+        Cursor::CharacterPositionBackward(n) => format!("Move cursor left {} (HPB)", columns(*n)),
+        Cursor::NextLine(n) => format!("Move cursor down {}, to column 1 (CNL)", rows(*n)),
+        Cursor::PrecedingLine(n) => format!("Move cursor up {}, to column 1 (CPL)", rows(*n)),
+        Cursor::Position { line, col } => format!(
+            "Move cursor to row {}, column {} (CUP)",
+            line.as_one_based(),
+            col.as_one_based()
+        ),
+        Cursor::CharacterAndLinePosition { line, col } => format!(
+            "Move cursor to row {}, column {} (HVP)",
+            line.as_one_based(),
+            col.as_one_based()
+        ),
+        Cursor::CharacterAbsolute(col) => {
+            format!("Move cursor to column {} (CHA)", col.as_one_based())
+        }
+        Cursor::CharacterPositionAbsolute(col) => {
+            format!("Move cursor to column {} (HPA)", col.as_one_based())
+        }
+        // termwiz doesn't turn this one's 0 into 1 like the others
+        Cursor::LinePositionAbsolute(line) => {
+            format!("Move cursor to row {} (VPA)", (*line).max(1))
+        }
+        _ => format!("Update cursor: {cursor:?}"),
+    }
+}
+
+fn rows(n: u32) -> String {
+    if n == 1 {
+        "1 row".into()
+    } else {
+        format!("{n} rows")
+    }
+}
+
+fn columns(n: u32) -> String {
+    if n == 1 {
+        "1 column".into()
+    } else {
+        format!("{n} columns")
+    }
+}
+
+fn tabulation_clear_description(clear: &TabulationClear) -> &'static str {
+    match clear {
+        TabulationClear::ClearCharacterTabStopAtActivePosition => {
+            "Clear the tab stop at the cursor"
+        }
+        TabulationClear::ClearLineTabStopAtActiveLine => {
+            "Clear the line tab stop at the cursor's row"
+        }
+        TabulationClear::ClearCharacterTabStopsAtActiveLine => {
+            "Clear the tab stops on the cursor's row"
+        }
+        TabulationClear::ClearAllCharacterTabStops => "Clear all tab stops",
+        TabulationClear::ClearAllLineTabStops => "Clear all line tab stops",
+        TabulationClear::ClearAllTabStops => "Clear all tab stops and line tab stops",
+    }
+}
+
+fn tabulation_control_description(control: &CursorTabulationControl) -> &'static str {
+    match control {
+        CursorTabulationControl::SetCharacterTabStopAtActivePosition => {
+            "Set a tab stop at the cursor"
+        }
+        CursorTabulationControl::SetLineTabStopAtActiveLine => {
+            "Set a line tab stop at the cursor's row"
+        }
+        CursorTabulationControl::ClearCharacterTabStopAtActivePosition => {
+            "Clear the tab stop at the cursor"
+        }
+        CursorTabulationControl::ClearLineTabstopAtActiveLine => {
+            "Clear the line tab stop at the cursor's row"
+        }
+        CursorTabulationControl::ClearAllCharacterTabStopsAtActiveLine => {
+            "Clear the tab stops on the cursor's row"
+        }
+        CursorTabulationControl::ClearAllCharacterTabStops => "Clear all tab stops",
+        CursorTabulationControl::ClearAllLineTabStops => "Clear all line tab stops",
+    }
+}
+
+fn tab_stops(n: u32) -> &'static str {
+    if n == 1 {
+        "tab stop"
+    } else {
+        "tab stops"
+    }
+}
+
+/// SGR attributes that termwiz doesn't know about. Hardly any terminal renders them
+fn unspecified_sgr_tooltip(params: &[CsiParam]) -> Option<&'static str> {
+    Some(match params {
+        [CsiParam::Integer(51)] => "Framed (SGR 51)",
+        [CsiParam::Integer(52)] => "Encircled (SGR 52)",
+        [CsiParam::Integer(54)] => "Not framed or encircled (SGR 54)",
+        [CsiParam::Integer(60)] => "Ideogram underline or right side line (SGR 60)",
+        [CsiParam::Integer(61)] => "Ideogram double underline or double right side line (SGR 61)",
+        [CsiParam::Integer(62)] => "Ideogram overline or left side line (SGR 62)",
+        [CsiParam::Integer(63)] => "Ideogram double overline or double left side line (SGR 63)",
+        [CsiParam::Integer(64)] => "Ideogram stress marking (SGR 64)",
+        [CsiParam::Integer(65)] => "Reset ideogram attributes (SGR 65)",
+        _ => return None,
+    })
+}
+
+/// termwiz doesn't model every CSI sequence; the ones it doesn't understand come through as
+/// `CSI::Unspecified` with the raw params and final byte, so we pick those apart ourselves
+fn unspecified_csi_to_dto(unspecified: &Unspecified, raw_bytes: String) -> VteEventDto {
+    if unspecified.control == 'm' {
+        if let Some(tooltip) = unspecified_sgr_tooltip(&unspecified.params) {
+            return VteEventDto::GenericEscape {
+                title: Some("SGR".into()),
+                icon_svg: None,
+                tooltip: Some(tooltip.into()),
+                raw_bytes,
+                cursor: None,
+            };
+        }
+    }
+
+    let (tooltip, icon_svg): (String, Option<String>) = match (
+        unspecified.params.as_slice(),
+        unspecified.control,
+    ) {
+        // DECSED: like ED but leaves characters protected with DECSCA alone
+        ([CsiParam::P(b'?')], 'J') | ([CsiParam::P(b'?'), CsiParam::Integer(0)], 'J') => (
+            "Selectively erase to end of display (unprotected only)".into(),
+            icon("erase"),
+        ),
+        ([CsiParam::P(b'?'), CsiParam::Integer(1)], 'J') => (
+            "Selectively erase to start of display (unprotected only)".into(),
+            icon("erase"),
+        ),
+        ([CsiParam::P(b'?'), CsiParam::Integer(2)], 'J') => (
+            "Selectively erase display (unprotected only)".into(),
+            icon("erase"),
+        ),
+        // DECSEL: like EL but leaves characters protected with DECSCA alone
+        ([CsiParam::P(b'?')], 'K') | ([CsiParam::P(b'?'), CsiParam::Integer(0)], 'K') => (
+            "Selectively erase to end of line (unprotected only)".into(),
+            icon("erase"),
+        ),
+        ([CsiParam::P(b'?'), CsiParam::Integer(1)], 'K') => (
+            "Selectively erase to start of line (unprotected only)".into(),
+            icon("erase"),
+        ),
+        ([CsiParam::P(b'?'), CsiParam::Integer(2)], 'K') => (
+            "Selectively erase line (unprotected only)".into(),
+            icon("erase"),
+        ),
+        // DECSCA: protect characters from DECSED/DECSEL
+        ([CsiParam::Integer(1), CsiParam::P(b'"')], 'q') => (
+            "Mark following characters as protected (DECSCA)".into(),
+            icon("shield"),
+        ),
+        ([CsiParam::P(b'"')], 'q') | ([CsiParam::Integer(0 | 2), CsiParam::P(b'"')], 'q') => (
+            "Mark following characters as unprotected (DECSCA)".into(),
+            icon("shield"),
+        ),
+        // XTSMTITLE and XTRMTITLE. Not to be confused with XTWINOPS, which has no `>`
+        ([CsiParam::P(b'>'), modes @ ..], control @ ('t' | 'T')) => {
+            let action = if control == 't' { "Set" } else { "Reset" };
+            (title_modes_tooltip(action, modes), icon("title"))
+        }
+        // DECXCPR: like DSR 6, but the reply includes the page number
+        ([CsiParam::P(b'?'), CsiParam::Integer(6)], 'n') => (
+            "Request extended cursor position report (DECXCPR, reply includes page number)".into(),
+            icon("cursor"),
+        ),
+        (
+            [CsiParam::P(b'?'), CsiParam::Integer(row), CsiParam::P(b';'), CsiParam::Integer(col), CsiParam::P(b';'), CsiParam::Integer(page)],
+            'R',
+        ) => (
+            format!(
+                "Extended cursor position report (DECXCPR): row {row}, column {col}, page {page}"
+            ),
+            icon("cursor"),
+        ),
+        // DECSWBV and DECSMBV
+        ([CsiParam::P(b' ')], control @ ('t' | 'u')) => {
+            (bell_volume_tooltip(control, 0), icon("bell"))
+        }
+        ([CsiParam::Integer(volume), CsiParam::P(b' ')], control @ ('t' | 'u')) => {
+            (bell_volume_tooltip(control, *volume), icon("bell"))
+        }
+        // XTMODKEYS with no resource, which termwiz doesn't parse
+        ([CsiParam::P(b'>')], 'm') => (
+            "Reset all key modifier resources to their defaults (XTMODKEYS)".into(),
+            icon("keyboard"),
+        ),
+        // the "disable key modifier options" counterpart of XTMODKEYS
+        ([CsiParam::P(b'>')], 'n') | ([CsiParam::P(b'>'), CsiParam::Integer(0)], 'n') => (
+            format!("Disable {} (XTMODKEYS off)", key_modifier_resource(0)),
+            icon("keyboard"),
+        ),
+        ([CsiParam::P(b'>'), CsiParam::Integer(resource)], 'n') => (
+            format!(
+                "Disable {} (XTMODKEYS off)",
+                key_modifier_resource(*resource)
+            ),
+            icon("keyboard"),
+        ),
+        // DECRPM: the terminal's reply to DECRQM
+        (
+            [CsiParam::P(b'?'), CsiParam::Integer(mode), CsiParam::P(b';'), CsiParam::Integer(status), CsiParam::P(b'$')],
+            'y',
+        ) => (mode_report_tooltip(true, *mode, *status), None),
+        (
+            [CsiParam::Integer(mode), CsiParam::P(b';'), CsiParam::Integer(status), CsiParam::P(b'$')],
+            'y',
+        ) => (mode_report_tooltip(false, *mode, *status), None),
+        // DECSCL
+        ([CsiParam::Integer(level), CsiParam::P(b'"')], 'p') => {
+            (conformance_level_tooltip(*level, None), None)
+        }
+        (
+            [CsiParam::Integer(level), CsiParam::P(b';'), CsiParam::Integer(controls), CsiParam::P(b'"')],
+            'p',
+        ) => (conformance_level_tooltip(*level, Some(*controls)), None),
+        // focus out; focus in comes through as CHT
+        ([], 'O') => (
+            "Terminal lost focus (with focus reporting, private mode 1004, on)".into(),
+            icon("focus"),
+        ),
+        // bracketed paste markers, which the terminal wraps pasted text in when mode 2004 is on
+        ([CsiParam::Integer(200)], '~') => (
+            "Start of bracketed paste: the text until the end marker was pasted".into(),
+            icon("paste"),
+        ),
+        ([CsiParam::Integer(201)], '~') => ("End of bracketed paste".into(), icon("paste")),
+        // MC (Media Copy): legacy printer control
+        ([], 'i') | ([CsiParam::Integer(0)], 'i') => ("Print screen (MC)".into(), icon("printer")),
+        ([CsiParam::Integer(4)], 'i') => (
+            "Turn off printer controller mode (MC)".into(),
+            icon("printer"),
+        ),
+        ([CsiParam::Integer(5)], 'i') => {
+            ("Enter printer controller mode (MC)".into(), icon("printer"))
+        }
+        ([CsiParam::P(b'?'), CsiParam::Integer(1)], 'i') => (
+            "Print the line containing the cursor (MC)".into(),
+            icon("printer"),
+        ),
+        ([CsiParam::P(b'?'), CsiParam::Integer(4)], 'i') => {
+            ("Turn off auto print (MC)".into(), icon("printer"))
+        }
+        ([CsiParam::P(b'?'), CsiParam::Integer(5)], 'i') => {
+            ("Turn on auto print (MC)".into(), icon("printer"))
+        }
+        ([CsiParam::P(b'?'), CsiParam::Integer(10)], 'i') => {
+            ("Print composed display (MC)".into(), icon("printer"))
+        }
+        ([CsiParam::P(b'?'), CsiParam::Integer(11)], 'i') => {
+            ("Print all pages (MC)".into(), icon("printer"))
+        }
+        // XTWINOPS operations that termwiz doesn't know
+        (params @ [CsiParam::Integer(_), ..], 't')
+            if params
+                .iter()
+                .all(|param| matches!(param, CsiParam::Integer(_) | CsiParam::P(b';'))) =>
+        {
+            let numbers: Vec<String> = params
+                .iter()
+                .filter_map(|param| match param {
+                    CsiParam::Integer(n) => Some(n.to_string()),
+                    _ => None,
+                })
+                .collect();
+            (
+                format!(
+                    "Unknown window operation (XTWINOPS) with parameters {}",
+                    numbers.join(";")
+                ),
+                icon("window"),
+            )
+        }
+        _ => {
+            return VteEventDto::GenericEscape {
+                title: Some("CSI".into()),
+                icon_svg: None,
+                tooltip: Some(format!("{unspecified:?}")),
+                raw_bytes,
+                cursor: None,
+            }
+        }
+    };
+
+    VteEventDto::GenericEscape {
+        title: None,
+        icon_svg,
+        tooltip: Some(tooltip),
+        raw_bytes,
+        cursor: None,
+    }
+}
+
+/// Convert escape code bytes into a user-facing string, replacing C0 control codes and DEL with
+/// their conventional escapes (`\r`, `\a`...) or `\xNN`. Printable text, including UTF-8, is left alone
+fn sanitize_raw_bytes(raw_bytes: &[u8]) -> String {
+    let mut sanitized = String::with_capacity(raw_bytes.len());
+    for c in String::from_utf8_lossy(raw_bytes).chars() {
+        match c {
+            '\0' => sanitized.push_str(r"\0"),
+            '\x07' => sanitized.push_str(r"\a"),
+            '\x08' => sanitized.push_str(r"\b"),
+            '\t' => sanitized.push_str(r"\t"),
+            '\n' => sanitized.push_str(r"\n"),
+            '\r' => sanitized.push_str(r"\r"),
+            '\x00'..='\x1f' | '\x7f' => sanitized.push_str(&format!(r"\x{:02x}", c as u32)),
+            c => sanitized.push(c),
+        }
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The DTOs for `bytes`, minus the invisible line breaks that only exist for layout
+    fn dtos(bytes: &[u8]) -> Vec<VteEventDto> {
+        actions_to_dtos(bytes)
+            .into_iter()
+            .filter(|dto| !matches!(dto, VteEventDto::InvisibleLineBreak {}))
+            .collect()
+    }
+
+    fn tooltip(dto: &VteEventDto) -> &str {
+        match dto {
+            VteEventDto::GenericEscape { tooltip, .. }
+            | VteEventDto::ColorEscape { tooltip, .. } => tooltip.as_deref().unwrap_or(""),
+            other => panic!("expected an escape, got {other:?}"),
+        }
+    }
+
+    /// Each printed string, with whatever `field` picks out of its DTO
+    fn prints(
+        bytes: &[u8],
+        field: impl Fn(&VteEventDto) -> Option<String>,
+    ) -> Vec<(String, Option<String>)> {
+        dtos(bytes)
+            .iter()
+            .filter_map(|dto| match dto {
+                VteEventDto::Print { string, .. } => Some((string.clone(), field(dto))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn color(dto: &VteEventDto) -> Option<String> {
+        match dto {
+            VteEventDto::Print { color, .. } => color.clone(),
+            _ => None,
+        }
+    }
+
+    fn underline(dto: &VteEventDto) -> Option<String> {
+        match dto {
+            VteEventDto::Print { underline, .. } => underline.clone(),
+            _ => None,
+        }
+    }
+
+    fn blink(dto: &VteEventDto) -> Option<String> {
+        match dto {
+            VteEventDto::Print { blink, .. } => blink.clone(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn consecutive_prints_are_merged() {
+        let dtos = dtos(b"hello world");
+        assert_eq!(dtos.len(), 1);
+        assert!(matches!(&dtos[0], VteEventDto::Print { string, .. } if string == "hello world"));
+    }
+
+    #[test]
+    fn sgr_foreground_colors_the_text_after_it() {
+        let red = hex_color(&ColorSpec::PaletteIndex(1)).unwrap();
+        let dtos = dtos(b"\x1b[31mred");
+        assert_eq!(dtos.len(), 2);
+        assert!(matches!(
+            &dtos[0],
+            VteEventDto::ColorEscape { title: Some(title), color, raw_bytes, .. }
+                if title == "FG" && *color == red && raw_bytes == r"\x1b[31m"
+        ));
+        assert!(matches!(
+            &dtos[1],
+            VteEventDto::Print { string, color: Some(color), .. } if string == "red" && *color == red
+        ));
+    }
+
+    #[test]
+    fn sgr_reset_goes_back_to_default_colors() {
+        let dtos = dtos(b"\x1b[41mbg\x1b[0mplain");
+        assert!(matches!(
+            dtos.last(),
+            Some(VteEventDto::Print { string, color: None, bg_color: None, .. }) if string == "plain"
+        ));
+    }
+
+    #[test]
+    fn several_sgr_attributes_at_once_are_one_dto() {
+        let dtos = dtos(b"\x1b[1;4m");
+        assert!(
+            matches!(&dtos[..], [VteEventDto::Sgr { attributes, .. }] if attributes.len() == 2)
+        );
+    }
+
+    #[test]
+    fn prints_only_merge_when_styled_the_same() {
+        let red = hex_color(&ColorSpec::PaletteIndex(1));
+        let blue = hex_color(&ColorSpec::PaletteIndex(4));
+        assert_eq!(
+            prints(b"\x1b[31ma\x1b[34mb", color),
+            [("a".to_string(), red), ("b".to_string(), blue)]
+        );
+
+        let print = |bytes: &[u8]| dtos(bytes).pop().unwrap();
+        let mut red_a = print(b"\x1b[31ma");
+        assert!(!red_a.merge_print(&print(b"\x1b[34mb")));
+        assert!(red_a.merge_print(&print(b"\x1b[31mb")));
+        assert!(matches!(&red_a, VteEventDto::Print { string, .. } if string == "ab"));
+    }
+
+    #[test]
+    fn blink_is_slow_rapid_or_off() {
+        assert_eq!(
+            prints(b"\x1b[5ma\x1b[6mb\x1b[25mc", blink),
+            [
+                ("a".to_string(), Some("slow".to_string())),
+                ("b".to_string(), Some("rapid".to_string())),
+                ("c".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn underline_styles() {
+        for (n, description, style) in [
+            (0, "Underline off", None),
+            (1, "Single underline", Some("single")),
+            (2, "Double underline", Some("double")),
+            (3, "Curly underline", Some("curly")),
+            (4, "Dotted underline", Some("dotted")),
+            (5, "Dashed underline", Some("dashed")),
+        ] {
+            let bytes = format!("\x1b[4:{n}mx");
+            assert_eq!(tooltip(&dtos(bytes.as_bytes())[0]), description, "4:{n}");
+            assert_eq!(
+                prints(bytes.as_bytes(), underline),
+                [("x".to_string(), style.map(String::from))],
+                "4:{n}"
+            );
+        }
+    }
+
+    #[test]
+    fn expanded_tabs_show_as_arrows_with_whitespace_shown() {
+        let mut builder = DtoBuilder::new(true, Some(4), false);
+        let mut dtos = vec![];
+        parse_bytes(
+            &mut Parser::new(),
+            &mut vec![],
+            b"a\tb",
+            |actions, raw_bytes| {
+                builder.append_sequence(&mut dtos, actions, raw_bytes);
+            },
+        );
+        assert!(matches!(
+            &dtos[0],
+            VteEventDto::Print { string, show_whitespace: true, .. } if string == "a→  "
+        ));
+    }
+
+    #[test]
+    fn cursor_position() {
+        let dtos = dtos(b"\x1b[5;10H");
+        assert_eq!(dtos.len(), 1);
+        assert!(tooltip(&dtos[0]).contains("Move cursor to row 5, column 10 (CUP)"));
+    }
+
+    #[test]
+    fn osc_8_hyperlinks_apply_to_the_text_between_them() {
+        let dtos = dtos(b"\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ after");
+        let linked: Vec<_> = dtos
+            .iter()
+            .filter_map(|dto| match dto {
+                VteEventDto::Print {
+                    string, hyperlink, ..
+                } => Some((string.as_str(), hyperlink.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            linked,
+            [("link", Some("https://example.com")), (" after", None)]
+        );
+    }
+
+    #[test]
+    fn control_codes() {
+        let dtos = dtos(b"a\x07\r\n");
+        assert_eq!(dtos.len(), 4);
+        assert!(
+            matches!(&dtos[1], VteEventDto::GenericEscape { icon_svg, .. } if *icon_svg == icon("bell"))
+        );
+        assert_eq!(dtos[2], VteEventDto::LineBreak { title: "CR".into() });
+        assert_eq!(dtos[3], VteEventDto::LineBreak { title: "LF".into() });
+    }
+
+    #[test]
+    fn decrqss_is_named() {
+        let dtos = dtos(b"\x1bP$q q\x1b\\");
+        assert!(matches!(
+            &dtos[0],
+            VteEventDto::GenericEscape { title: Some(title), .. } if title == "DECRQSS"
+        ));
+    }
+
+    #[test]
+    fn short_dcs_strings_take_their_st_along() {
+        let mut builder = DtoBuilder::new(false, None, false);
+        let mut dtos = vec![];
+        parse_bytes(
+            &mut Parser::new(),
+            &mut vec![],
+            b"\x1bP$q q\x1b\\",
+            |actions, raw_bytes| {
+                builder.append_sequence(&mut dtos, actions, raw_bytes);
+            },
+        );
+        builder.finish_dcs(&mut dtos);
+        assert!(matches!(
+            &dtos[..],
+            [VteEventDto::GenericEscape { title: Some(title), raw_bytes, .. }]
+                if title == "DECRQSS" && raw_bytes == r"\x1bP$q q\x1b\"
+        ));
+    }
+
+    #[test]
+    fn dcs_strings_are_decoded_whole() {
+        let dtos = dtos(b"\x1bP1$r0m\x1b\\");
+        assert_eq!(dtos.len(), 1);
+        assert_eq!(
+            tooltip(&dtos[0]),
+            "Status string report: SGR (text attributes) is \"0\""
+        );
+    }
+
+    #[test]
+    fn tab_stop_counts() {
+        assert!(tooltip(&dtos(b"\x1b[2I")[0]).contains("Cursor forward 2 tab stops"));
+        assert!(tooltip(&dtos(b"\x1b[3Z")[0]).contains("Cursor backward 3 tab stops"));
+        for (bytes, expected) in [
+            (&b"\x1b[1I"[..], "Cursor forward 1 tab stop"),
+            (b"\x1b[Z", "Cursor backward 1 tab stop"),
+        ] {
+            let dtos = dtos(bytes);
+            let tooltip = tooltip(&dtos[0]);
+            assert!(
+                tooltip.contains(expected) && !tooltip.contains("stops"),
+                "{tooltip}"
+            );
+        }
+        // a bare CSI I could be a focus report too
+        assert!(tooltip(&dtos(b"\x1b[I")[0]).contains("cursor forward 1 tab stop (CHT)"));
+    }
+
+    #[test]
+    fn xtwinops_resize() {
+        let dtos = dtos(b"\x1b[8;24;80t");
+        assert_eq!(
+            tooltip(&dtos[0]),
+            "Resize terminal to 24 rows × 80 cols (XTWINOPS)"
+        );
+    }
+
+    #[test]
+    fn utf8_split_across_reads_stays_together() {
+        let mut parser = Parser::new();
+        let mut pending = vec![];
+        let mut sequences = vec![];
+        for chunk in ["é".as_bytes()[..1].to_vec(), "é".as_bytes()[1..].to_vec()] {
+            parse_bytes(&mut parser, &mut pending, &chunk, |actions, raw_bytes| {
+                sequences.push((actions, raw_bytes))
+            });
+        }
+        assert_eq!(
+            sequences,
+            [(vec![Action::Print('é')], "é".as_bytes().to_vec())]
+        );
+    }
+
+    #[test]
+    fn three_byte_utf8_split_either_way_stays_together() {
+        let euro = "€".as_bytes();
+        for split in 1..euro.len() {
+            let mut parser = Parser::new();
+            let mut pending = vec![];
+            let mut sequences = vec![];
+            for chunk in [&euro[..split], &euro[split..]] {
+                parse_bytes(&mut parser, &mut pending, chunk, |actions, raw_bytes| {
+                    sequences.push((actions, raw_bytes))
+                });
+            }
+            assert_eq!(sequences, [(vec![Action::Print('€')], euro.to_vec())]);
+        }
+    }
+
+    #[test]
+    fn raw_bytes_are_sanitized() {
+        assert_eq!(
+            sanitize_raw_bytes("\x1b[0m\r\n\t\x07\x7f\0\x01é".as_bytes()),
+            r"\x1b[0m\r\n\t\a\x7f\0\x01é"
+        );
+    }
+
+    /// A cheap stand-in for the fuzz target that runs with the other tests: random bytes, weighted
+    /// towards the ones escape sequences are made of, mustn't panic, leave control codes in the raw bytes
+    /// or produce invalid JSON
+    #[test]
+    fn random_bytes_decode_to_valid_json() {
+        const ALPHABET: &[u8] =
+            b"\x1b\x1b\x1b[[]]P\\0123456789;:?$+>=m;q\x07\x08\t\n\r\x7f\x9b\xc3\xa9\xe2\x82\xac";
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let len = next() % 48;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match next() % 4 {
+                    0 => next() as u8,
+                    _ => ALPHABET[next() as usize % ALPHABET.len()],
+                })
+                .collect();
+            let sanitized = sanitize_raw_bytes(&bytes);
+            assert!(
+                !sanitized.chars().any(|c| c.is_ascii_control()),
+                "{bytes:?} sanitized to {sanitized:?}"
+            );
+            for dto in actions_to_dtos(&bytes) {
+                for minimal in [false, true] {
+                    let json = serialize_dtos(&[&dto], minimal);
+                    assert!(
+                        serde_json::from_str::<serde_json::Value>(&json).is_ok(),
+                        "{bytes:?} gave invalid JSON: {json}"
+                    );
+                }
+            }
+        }
+    }
+}
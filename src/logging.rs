@@ -0,0 +1,55 @@
+//! A tiny file logger for troubleshooting escape-artist itself, enabled with `--verbose`.
+//! Logs go to a file because stdout belongs to the child process and the terminal is in raw mode.
+
+use std::{fs::File, io::Write, sync::Mutex, time::Instant};
+
+use anyhow::Result;
+use log::{LevelFilter, Log, Metadata, Record};
+
+pub const LOG_FILE: &str = "escape-artist.log";
+
+struct FileLogger {
+    file: Mutex<File>,
+    start: Instant,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // dependencies (axum, hyper etc.) are noisy and not what we're debugging
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(
+                file,
+                "{:>4}.{:03}s {:<5} {}",
+                elapsed.as_secs(),
+                elapsed.subsec_millis(),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Start logging to [`LOG_FILE`]. Without this, all log macros are no-ops
+pub fn init() -> Result<()> {
+    let logger = FileLogger {
+        file: Mutex::new(File::create(LOG_FILE)?),
+        start: Instant::now(),
+    };
+    log::set_boxed_logger(Box::new(logger))?;
+    log::set_max_level(LevelFilter::Trace);
+    Ok(())
+}
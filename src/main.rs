@@ -1,157 +1,496 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fs::File,
-    io::{stdout, Read, Write},
+    io::{stdout, BufRead, BufReader, BufWriter, Cursor, ErrorKind, Read, Write},
     mem::take,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     sync::{
-        atomic::{AtomicBool, AtomicI64, Ordering},
+        atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
-use ansi_colours::rgb_from_ansi256;
+mod cast;
+mod diff;
+mod logging;
+mod static_view;
+
 use anyhow::{bail, Result};
 use axum::{
     body::Body,
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        Query, State, WebSocketUpgrade,
     },
     http::{header, Response, StatusCode, Uri},
-    response::{Html, IntoResponse},
-    routing::get,
+    response::{Html, IntoResponse, Json},
+    routing::{get, post},
     Router,
 };
+use cast::{CastWriter, TimedReader};
 use clap::{
     builder::{StyledStr, Styles},
     Parser as ClapParser,
 };
 use crossterm::{cursor, execute, style::Stylize, terminal};
+use escape_artist::{
+    action_category, hex_color, icons, is_cursor_move, is_undecoded, parse_bytes, reset_kind,
+    screen::VirtualScreen, serialize_dtos, DiffSide, DtoBuilder, Sequence, VteEventDto,
+};
+use flate2::read::MultiGzDecoder;
+use log::{debug, info, trace, warn};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use rust_embed::RustEmbed;
-use serde::Serialize;
-use termwiz::{
-    color::ColorSpec,
-    escape::{
-        csi::{Edit, EraseInDisplay, EraseInLine, Sgr},
-        parser::Parser,
-        Action, ControlCode, Esc, EscCode, OperatingSystemCommand, CSI,
-    },
+use serde::{Deserialize, Serialize};
+use termwiz::escape::{
+    csi::{Edit, Sgr},
+    parser::Parser,
+    Action, CSI,
 };
 use tokio::{
     net::TcpListener,
     sync::{
-        broadcast,
+        broadcast::{self, error::RecvError},
         mpsc::{channel, Receiver, Sender},
-        Mutex,
+        watch, Mutex,
     },
-    time::{timeout_at, Instant},
+    time::{sleep, sleep_until, timeout, timeout_at, Instant},
 };
 
 #[derive(clap::Parser, Clone)]
-#[command(author, version, about, long_about = None, styles = clap_v3_style(), after_help = after_help())]
+#[command(author, version, about, long_about = None, styles = clap_v3_style(), after_help = after_help(), args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Arguments for `run`, which is what happens when no subcommand is given
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(clap::Subcommand, Clone)]
+enum Command {
+    /// Launch a command in a pty and view its escape codes (the default)
+    Run(RunArgs),
+    /// View the escape codes in a recording of terminal output
+    Replay(ReplayArgs),
+    /// Print the decoded escape codes in a recording, one per line
+    Explain {
+        /// The file to explain
+        file: String,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
+    },
+    /// Compare the escape sequences in two recordings. Exits with an error if they differ
+    Diff {
+        /// The original recording
+        a: String,
+        /// The recording to compare it to
+        b: String,
+
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        output_format: DiffFormat,
+
+        /// Show the differences in the web UI instead of printing them
+        #[arg(long, default_value = "false", conflicts_with = "output_format")]
+        web: bool,
+
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+    /// Play a recording back into this terminal by writing it to stdout
+    Play {
+        /// The file to play
+        file: String,
+
+        /// Milliseconds to pause after each line. Recordings don't store timing, so this is the
+        /// only pacing available
+        #[arg(long, default_value_t = 0)]
+        line_delay_ms: u64,
+    },
+    /// List the escape sequences in a recording that could not be decoded. Exits with an error if there are any
+    Check {
+        /// The file to check
+        file: String,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum OutputFormat {
+    /// One human-readable line per escape sequence or run of text
+    Text,
+    /// An array of DTOs, like the ones the web UI receives
+    Json,
+    /// offset,category,title,tooltip,raw_bytes columns
+    Csv,
+    /// termwiz's `Action` for everything parsed, one per line, like wezterm's debug output. Handy for
+    /// cross-referencing with wezterm or reporting parser issues upstream
+    ActionDebug,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum DiffFormat {
+    /// The first divergence, then every mismatch as -/+ lines
+    Text,
+    /// The first divergence and every mismatch as JSON
+    Json,
+}
+
+#[derive(clap::Args, Clone)]
+struct ServerArgs {
     /// The port for the web server
     #[arg(short, long, default_value = "3000")]
     port: u16,
 
-    #[arg(short, long)]
-    replay_file: Option<String>,
+    /// The address for the web server to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: IpAddr,
+
+    /// Ping idle websocket clients this often so proxies don't close the connection. 0 disables
+    #[arg(long, default_value = "30", value_name = "SECS")]
+    keepalive_secs: u64,
+
+    /// Keep at most N events in memory, dropping the oldest ones once there are more. Clients are
+    /// told how many were dropped
+    #[arg(long, default_value = "100000", value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_events: u64,
+}
+
+impl ServerArgs {
+    fn keepalive(&self) -> Option<Duration> {
+        (self.keepalive_secs > 0).then(|| Duration::from_secs(self.keepalive_secs))
+    }
+
+    fn url(&self) -> String {
+        if self.host.is_loopback() || self.host.is_unspecified() {
+            format!("http://localhost:{}", self.port)
+        } else {
+            format!("http://{}", SocketAddr::from((self.host, self.port)))
+        }
+    }
+}
+
+#[derive(clap::Args, Clone)]
+struct RunArgs {
+    #[command(flatten)]
+    server: ServerArgs,
 
     /// Log stdout to a file (stdout.txt)
     #[arg(short, long, default_value = "false")]
     log_to_file: bool,
 
+    /// Also record the command's output as an asciinema v2 recording, with its timing, which
+    /// `escape-artist replay` and asciinema itself can read
+    #[arg(long, value_name = "PATH")]
+    record_cast: Option<String>,
+
+    /// Make whitespace in printed text visible (spaces are shown as middots)
+    #[arg(long, default_value = "false")]
+    show_whitespace: bool,
+
+    /// Expand tabs in printed text to spaces, with tab stops every N columns
+    #[arg(long, value_name = "N")]
+    expand_tabs: Option<usize>,
+
+    /// A JSON file mapping icon names (like "bell") to inline SVGs or other built-in icon names
+    #[arg(long, value_name = "FILE")]
+    icons: Option<String>,
+
+    /// Log diagnostics for escape-artist itself to escape-artist.log
+    #[arg(short, long, default_value = "false")]
+    verbose: bool,
+
+    /// Leave the terminal in its normal (cooked) mode instead of switching to raw mode. Input is still
+    /// forwarded to the command, but only a line at a time. Useful when stdin isn't a terminal
+    #[arg(long, default_value = "false")]
+    no_raw_mode: bool,
+
+    /// Collapse runs of cursor movements into one event with the net displacement. A run shows up once
+    /// something other than a cursor movement follows it
+    #[arg(long, default_value = "false")]
+    summarize_cursor: bool,
+
+    /// Stop decoding after N escape sequences (a run of printed text counts as one), e.g. to focus on
+    /// what a program does at startup. The command keeps running
+    #[arg(long, value_name = "N")]
+    head: Option<usize>,
+
+    /// Note how long after each input the command's first output came, for profiling how responsive
+    /// it is. The notes show up like annotations
+    #[arg(long, default_value = "false")]
+    input_latency: bool,
+
+    /// Write a JSON summary of the session (command, timings, counts, exit code, files written) here on exit
+    #[arg(long, value_name = "PATH")]
+    manifest: Option<String>,
+
+    /// Decode a recording before the command's output, so they show up as one timeline. Handy for
+    /// replaying the setup for a bug and then carrying on live
+    #[arg(long, value_name = "PATH")]
+    prelude_file: Option<String>,
+
+    /// Also write the --prelude-file recording to the terminal, so the command starts with its
+    /// effects on screen
+    #[arg(long, default_value = "false", requires = "prelude_file")]
+    render_prelude: bool,
+
+    /// Set TERM for the command, to see how it behaves in a different kind of terminal
+    #[arg(long, value_name = "VALUE")]
+    term: Option<String>,
+
+    /// Set an environment variable for the command. Can be repeated
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+
+    /// Decode whatever is piped in instead of launching a command, e.g. `some-tool | escape-artist --stdin`.
+    /// The output is passed through to stdout, and escape-artist exits once the pipe is closed
+    #[arg(long, default_value = "false", conflicts_with = "argv")]
+    stdin: bool,
+
+    /// With --stdin, write each event to stdout as a line of JSON as soon as it's decoded, instead of
+    /// passing the output through. Nothing else is written to stdout, so it can be piped into other tools
+    #[arg(long, default_value = "false", requires = "stdin")]
+    explain_json: bool,
+
     /// Command to be launched, optionally with args. If not specified, will use the $SHELL environment variable
     #[arg(last = true)]
     argv: Vec<String>,
 }
 
-fn main() -> Result<()> {
-    initialize_environment();
-    let resize_signaled = Arc::new(AtomicBool::new(false));
+#[derive(clap::Args, Clone)]
+struct ReplayArgs {
+    /// The file to replay, which can be gzipped
+    file: String,
 
-    // No SIGWINCH on Windows, but it seems like there's no great alternative: https://github.com/microsoft/terminal/issues/281
-    #[cfg(not(windows))]
-    {
-        use signal_hook::consts::SIGWINCH;
-        let _ = signal_hook::flag::register(SIGWINCH, resize_signaled.clone());
-    }
+    #[command(flatten)]
+    server: ServerArgs,
 
-    let cli = Cli::parse();
+    /// Make whitespace in printed text visible (spaces are shown as middots)
+    #[arg(long, default_value = "false")]
+    show_whitespace: bool,
+
+    /// Expand tabs in printed text to spaces, with tab stops every N columns
+    #[arg(long, value_name = "N")]
+    expand_tabs: Option<usize>,
+
+    /// Collapse runs of cursor movements into one event with the net displacement
+    #[arg(long, default_value = "false")]
+    summarize_cursor: bool,
+
+    /// Stop decoding after N escape sequences (a run of printed text counts as one)
+    #[arg(long, value_name = "N")]
+    head: Option<usize>,
+
+    /// Play back an asciinema recording at the pace it was recorded, sped up by this factor. 0 or inf
+    /// replays it all at once, like other recordings
+    #[arg(long, default_value = "1.0", value_name = "FACTOR", value_parser = parse_speed)]
+    speed: f64,
+
+    /// Reload the file whenever it changes on disk
+    #[arg(long, default_value = "false")]
+    watch_file: bool,
 
-    if cli.replay_file.is_some() && !cli.argv.is_empty() {
-        bail!("Cannot specify a replay file and a command to run at the same time")
+    /// Keep reading the file as another program appends to it, like `tail -f`. asciinema recordings
+    /// are still read once
+    #[arg(long, default_value = "false", conflicts_with_all = ["watch_file", "text"])]
+    follow: bool,
+
+    /// Parse the whole file in one go as UTF-8 text (faster for big files). Raw bytes are
+    /// re-encoded from the parsed escape codes, so they may not match the file exactly.
+    /// Falls back to the normal byte-by-byte parsing if the file isn't valid UTF-8
+    #[arg(long, default_value = "false")]
+    text: bool,
+
+    /// A JSON file mapping icon names (like "bell") to inline SVGs or other built-in icon names
+    #[arg(long, value_name = "FILE")]
+    icons: Option<String>,
+
+    /// Log diagnostics for escape-artist itself to escape-artist.log
+    #[arg(short, long, default_value = "false")]
+    verbose: bool,
+}
+
+fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err(format!("expected KEY=VALUE, got {s:?}")),
     }
+}
 
-    let (tx, _) = broadcast::channel::<VteEventDto>(10000); // capacity arbitrarily chosen
-    let state = AppState {
-        sequence_count: Arc::new(AtomicI64::new(0)),
-        all_dtos: Arc::new(Mutex::new(vec![])),
-        tx,
-    };
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed >= 0.0 => Ok(speed),
+        _ => Err(format!("expected a speed factor like 2 or 0.5, got {s:?}")),
+    }
+}
+
+fn main() -> Result<()> {
+    initialize_environment();
+
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Run(cli.run)) {
+        Command::Run(args) => run(args),
+        Command::Replay(args) => replay(args),
+        Command::Explain {
+            file,
+            output_format,
+        } => explain(&file, output_format),
+        Command::Diff {
+            a,
+            b,
+            web: true,
+            server,
+            ..
+        } => serve_diff(&a, &b, server),
+        Command::Diff {
+            a,
+            b,
+            output_format,
+            ..
+        } => diff_files(&a, &b, output_format),
+        Command::Play {
+            file,
+            line_delay_ms,
+        } => play(&file, Duration::from_millis(line_delay_ms)),
+        Command::Check { file } => check(&file),
+    }
+}
 
+fn replay(args: ReplayArgs) -> Result<()> {
+    if args.verbose {
+        logging::init()?;
+        info!("replaying {}", args.file);
+    }
+    if let Some(path) = &args.icons {
+        icons::load_overrides(path)?;
+    }
+    let state = AppState::new(args.server.keepalive(), args.server.max_events as usize);
     let runtime = tokio::runtime::Runtime::new()?;
 
-    if let Some(file) = &cli.replay_file {
+    println!(
+        "{}{}{}{} 🎨",
+        "Replaying ".cyan(),
+        args.file.clone().magenta(),
+        " in Escape Artist v".cyan(),
+        env!("CARGO_PKG_VERSION").cyan(),
+    );
+    // fail early if the file can't be read, rather than in the parsing thread
+    let contents = read_recording(&args.file)?;
+    if args.text && std::str::from_utf8(&contents).is_err() {
         println!(
-            "{}{}{}{} 🎨",
-            "Replaying ".cyan(),
-            file.clone().magenta(),
-            " in Escape Artist v".cyan(),
-            env!("CARGO_PKG_VERSION").cyan(),
+            "{}",
+            format!("{} is not valid UTF-8, parsing it byte by byte", args.file).yellow()
         );
-        let (action_sender, action_receiver) = channel::<(Action, Vec<u8>)>(10000);
+    }
 
-        let reader = File::open(file)?;
-        // Watch the child's output, pump it into the VTE parser/performer, and forward it to the terminal
-        // We use a thread here because reading from the pty is blocking
-        thread::spawn(move || {
-            parse_raw_output(cli.log_to_file, false, Box::new(reader), action_sender)
-        });
+    drop(contents);
 
-        let cloned_state = state.clone();
-        runtime.spawn(process_actions(action_receiver, cloned_state));
+    let server = args.server.clone();
+    let handle = runtime.handle().clone();
+    let speed = (args.speed > 0.0 && args.speed.is_finite()).then_some(args.speed);
+    let cloned_state = state.clone();
+    // Read the recording, pump it into the VTE parser/performer, and do it again whenever it changes if
+    // --watch-file is set. We use a thread here because reading from the file is blocking
+    thread::spawn(move || loop {
+        let (action_sender, action_receiver) = channel::<Sequence>(10000);
+        let processing = handle.spawn(process_actions(
+            action_receiver,
+            cloned_state.clone(),
+            DtoBuilder::new(
+                args.show_whitespace,
+                args.expand_tabs,
+                args.summarize_cursor,
+            ),
+            args.head,
+            false,
+            true,
+        ));
+        let modified = modified_time(&args.file);
+        if let Err(e) = parse_recording(
+            &args.file,
+            args.text,
+            speed,
+            args.follow,
+            action_sender,
+            cloned_state.clone(),
+        ) {
+            warn!("failed to parse {}: {e}", args.file);
+        }
+        if !args.watch_file {
+            break;
+        }
 
-        println!(
-            "{}{}{}",
-            "Open ".cyan(),
-            format!("http://localhost:{}", &cli.port).magenta(),
-            " to view terminal escape codes, type CTRL+D to exit".cyan()
-        );
+        let _ = handle.block_on(processing);
+        wait_for_change(&args.file, modified);
+        info!("{} changed, reloading it", args.file);
+        handle.block_on(cloned_state.clear());
+    });
 
-        terminal::enable_raw_mode()?;
-        let _clean_up = CleanUp;
+    println!(
+        "{}{}{}",
+        "Open ".cyan(),
+        server.url().magenta(),
+        " to view terminal escape codes, type CTRL+D to exit".cyan()
+    );
 
-        // start web server and attempt to open it in browser
-        let cloned_state = state.clone();
-        runtime.spawn(run_webserver(cloned_state, cli));
+    terminal::enable_raw_mode()?;
+    let _clean_up = CleanUp { raw_mode: true };
 
-        // read stdin, exit on ctrl+d
-        let mut stdin = std::io::stdin();
-        let mut buffer = [0; 1024];
-        loop {
-            let n = stdin.read(&mut buffer)?;
-            let bytes = buffer[..n].to_vec();
-            if bytes.iter().any(|b| *b == 0x4) {
-                // EOF
-                break;
-            }
+    // start web server and attempt to open it in browser
+    let cloned_state = state.clone();
+    runtime.spawn(run_webserver(cloned_state, server));
+
+    wait_for_ctrl_d()
+}
+
+/// Read stdin (in raw mode) until the user types CTRL+D
+fn wait_for_ctrl_d() -> Result<()> {
+    let mut stdin = std::io::stdin();
+    let mut buffer = [0; 1024];
+    loop {
+        let n = stdin.read(&mut buffer)?;
+        let bytes = buffer[..n].to_vec();
+        if bytes.contains(&0x4) {
+            // EOF
+            return Ok(());
         }
+    }
+}
+
+fn run(args: RunArgs) -> Result<()> {
+    if args.verbose {
+        logging::init()?;
+    }
+    if let Some(path) = &args.icons {
+        icons::load_overrides(path)?;
+    }
+    if args.stdin {
+        return run_piped(args);
+    }
+    let resize_signaled = Arc::new(AtomicBool::new(false));
 
-        return Ok(());
+    // No SIGWINCH on Windows, but it seems like there's no great alternative: https://github.com/microsoft/terminal/issues/281
+    #[cfg(not(windows))]
+    {
+        use signal_hook::consts::SIGWINCH;
+        let _ = signal_hook::flag::register(SIGWINCH, resize_signaled.clone());
     }
 
-    let argv = if cli.argv.is_empty() {
+    let state = AppState::new(args.server.keepalive(), args.server.max_events as usize);
+    let runtime = tokio::runtime::Runtime::new()?;
+    let started_at = SystemTime::now();
+
+    let argv = if args.argv.is_empty() {
         if let Ok(shell) = std::env::var("SHELL") {
             vec![shell]
         } else {
-            bail!("SHELL environment variable not found; either set it or use --shell")
+            bail!("SHELL environment variable not found; either set it or pass a command after --")
         }
     } else {
-        cli.argv.clone()
+        args.argv.clone()
     };
 
     println!(
@@ -162,25 +501,45 @@ fn main() -> Result<()> {
         env!("CARGO_PKG_VERSION").cyan(),
     );
 
+    // fail early if the prelude can't be read, rather than in the parsing thread
+    let prelude = args
+        .prelude_file
+        .as_deref()
+        .map(|file| open_recording(file, None))
+        .transpose()?;
+
     let pty_system = native_pty_system();
 
-    let (cols, rows) = terminal::size()?;
+    let (mut cols, mut rows) = terminal::size()?;
+    let cast_writer = args
+        .record_cast
+        .as_deref()
+        .map(|path| CastWriter::create(path, (cols, rows)))
+        .transpose()?;
     let pair = pty_system.openpty(PtySize {
         rows,
         cols,
         pixel_width: 0,
         pixel_height: 0,
     })?;
+    state
+        .screen
+        .blocking_lock()
+        .resize(cols as usize, rows as usize);
 
     println!(
         "{}{}{}",
         "Open ".cyan(),
-        format!("http://localhost:{}", &cli.port).magenta(),
+        args.server.url().magenta(),
         " to view terminal escape codes, type CTRL+D to exit".cyan()
     );
     println!();
-    terminal::enable_raw_mode()?;
-    let _clean_up = CleanUp;
+    if !args.no_raw_mode {
+        terminal::enable_raw_mode()?;
+    }
+    let _clean_up = CleanUp {
+        raw_mode: !args.no_raw_mode,
+    };
 
     let mut stdin = std::io::stdin();
 
@@ -189,34 +548,99 @@ fn main() -> Result<()> {
     if let Ok(cwd) = std::env::current_dir() {
         command.cwd(cwd);
     }
+    if let Some(term) = &args.term {
+        command.env("TERM", term);
+    }
+    for (key, value) in &args.env_vars {
+        command.env(key, value);
+    }
 
     // Spawn the child process (shell usually), wired up to the PTY
-    let child = pair.slave.spawn_command(command)?;
+    let mut child = pair.slave.spawn_command(command)?;
+    let mut killer = child.clone_killer();
+    // otherwise we hold the pty open and never see EOF when the child exits
+    drop(pair.slave);
+    info!("launched {argv:?} in a {cols}x{rows} pty");
     // This reads output (stderr and stdout multiplexed into 1 stream) from child
-    let mut reader = pair.master.try_clone_reader()?;
-
-    if let Some(file) = &cli.replay_file {
-        reader = Box::new(std::fs::File::open(file)?);
-    }
+    let reader = pair.master.try_clone_reader()?;
 
-    let (action_sender, action_receiver) = channel::<(Action, Vec<u8>)>(10000);
+    let (action_sender, action_receiver) = channel::<Sequence>(10000);
 
     // Watch the child's output, pump it into the VTE parser/performer, and forward it to the terminal
     // We use a thread here because reading from the pty is blocking
-    thread::spawn(move || parse_raw_output(cli.log_to_file, true, reader, action_sender));
+    let log_to_file = args.log_to_file;
+    let render_prelude = args.render_prelude;
+    let cloned_state = state.clone();
+    thread::spawn(move || {
+        // the prelude goes through the same channel first; the child's output waits in the pty until it's done
+        if let Some(prelude) = prelude {
+            if let Err(e) = parse_raw_output(
+                false,
+                None,
+                render_prelude,
+                prelude,
+                false,
+                action_sender.clone(),
+                cloned_state.clone(),
+            ) {
+                warn!("failed to parse the prelude: {e}");
+            }
+        }
+        parse_raw_output(
+            log_to_file,
+            cast_writer,
+            true,
+            reader,
+            false,
+            action_sender,
+            cloned_state,
+        )
+    });
 
     let cloned_state = state.clone();
-    runtime.spawn(process_actions(action_receiver, cloned_state));
+    let processing = runtime.spawn(process_actions(
+        action_receiver,
+        cloned_state,
+        DtoBuilder::new(
+            args.show_whitespace,
+            args.expand_tabs,
+            args.summarize_cursor,
+        ),
+        args.head,
+        false,
+        false,
+    ));
+
+    // let the web UI know when the child exits
+    let cloned_state = state.clone();
+    runtime.spawn(async move {
+        let status = tokio::task::spawn_blocking(move || child.wait()).await;
+        let exit_code = match status {
+            Ok(Ok(status)) => Some(status.exit_code() as i32),
+            _ => None,
+        };
+        info!("child exited with code {exit_code:?}");
+        // give the last of the child's output a chance to be processed first. This can take forever if
+        // something else (like a background job) is keeping the pty open, so don't wait too long
+        let _ = timeout(Duration::from_secs(1), processing).await;
+        let sequence_count = cloned_state.sequence_count.load(Ordering::Relaxed);
+        cloned_state
+            .push_dto(VteEventDto::SessionEnded {
+                exit_code,
+                sequence_count,
+            })
+            .await;
+    });
 
     // start web server and attempt to open it in browser
     let cloned_state = state.clone();
-    let _webserver = runtime.spawn(run_webserver(cloned_state, cli));
+    let _webserver = runtime.spawn(run_webserver(cloned_state, args.server));
 
     let mut child_stdin = pair.master.take_writer()?;
     // forward all input from this process to the child
     loop {
         if resize_signaled.load(Ordering::Relaxed) {
-            let (cols, rows) = terminal::size()?;
+            (cols, rows) = terminal::size()?;
             pair.master
                 .resize(PtySize {
                     rows,
@@ -225,18 +649,39 @@ fn main() -> Result<()> {
                     pixel_height: 0,
                 })
                 .unwrap();
+            state
+                .screen
+                .blocking_lock()
+                .resize(cols as usize, rows as usize);
+            debug!("resized pty to {cols}x{rows}");
             resize_signaled.store(false, Ordering::Relaxed);
         }
 
         let mut buffer = [0; 1024];
         let n = stdin.read(&mut buffer[..])?;
         let bytes = buffer[..n].to_vec();
+        if args.input_latency && n > 0 {
+            let now_ms = state.start.elapsed().as_millis() as i64;
+            state.last_input_ms.store(now_ms, Ordering::Relaxed);
+        }
         child_stdin.write_all(&bytes)?;
 
-        if bytes.iter().any(|b| *b == 0x4) {
+        // without raw mode, CTRL+D (or the end of piped input) shows up as a 0-byte read instead
+        if n == 0 || bytes.contains(&0x4) {
             // EOF
-            _ = child.clone_killer().kill();
+            _ = killer.kill();
             drop(_clean_up);
+            if let Some(path) = &args.manifest {
+                let files = args
+                    .log_to_file
+                    .then(|| "stdout.txt".to_string())
+                    .into_iter()
+                    .chain(args.record_cast.clone())
+                    .collect();
+                let manifest = Manifest::new(&state, argv, started_at, (cols, rows), files);
+                serde_json::to_writer_pretty(File::create(path)?, &manifest)?;
+                info!("wrote session manifest to {path}");
+            }
             let sequence_count = state.sequence_count.load(Ordering::Relaxed);
             println!(
                 "\n{}{}",
@@ -249,15 +694,500 @@ fn main() -> Result<()> {
     }
 }
 
-async fn run_webserver(cloned_state: AppState, cli: Cli) {
+/// `run` with `--stdin`: there's no pty or command, just output that's already been written somewhere else
+fn run_piped(args: RunArgs) -> Result<()> {
+    let state = AppState::new(args.server.keepalive(), args.server.max_events as usize);
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    // stdout is all JSON with --explain-json
+    let banners = !args.explain_json;
+    if banners {
+        println!(
+            "{}{} 🎨",
+            "Reading stdin in Escape Artist v".cyan(),
+            env!("CARGO_PKG_VERSION").cyan(),
+        );
+    }
+    // stdout may not be a terminal either, in which case any size will do for the recording
+    let size = terminal::size().unwrap_or((80, 24));
+    let cast_writer = args
+        .record_cast
+        .as_deref()
+        .map(|path| CastWriter::create(path, size))
+        .transpose()?;
+    if banners {
+        println!(
+            "{}{}{}",
+            "Open ".cyan(),
+            args.server.url().magenta(),
+            " to view terminal escape codes, close stdin to exit".cyan()
+        );
+        println!();
+    }
+
+    let (action_sender, action_receiver) = channel::<Sequence>(10000);
+    let processing = runtime.spawn(process_actions(
+        action_receiver,
+        state.clone(),
+        DtoBuilder::new(
+            args.show_whitespace,
+            args.expand_tabs,
+            args.summarize_cursor,
+        ),
+        args.head,
+        args.explain_json,
+        false,
+    ));
+    let _webserver = runtime.spawn(run_webserver(state.clone(), args.server));
+
+    let parsed = parse_raw_output(
+        args.log_to_file,
+        cast_writer,
+        !args.explain_json,
+        Box::new(std::io::stdin()),
+        false,
+        action_sender,
+        state.clone(),
+    );
+    // the count should include the last of the output
+    let _ = runtime.block_on(processing);
+    if banners {
+        let sequence_count = state.sequence_count.load(Ordering::Relaxed);
+        println!(
+            "\n{}{}",
+            "Exited. Processed ".cyan(),
+            format!("{} escape sequences", sequence_count).magenta()
+        );
+    }
+    parsed
+}
+
+/// What `--manifest` writes on exit, for tools that want to know what happened in a session
+#[derive(Serialize)]
+struct Manifest {
+    argv: Vec<String>,
+    /// Unix timestamps in ms
+    started_at_ms: u128,
+    ended_at_ms: u128,
+    byte_count: usize,
+    sequence_count: i64,
+    /// How many events came from each kind of action, keyed by `action_category`
+    category_counts: BTreeMap<&'static str, usize>,
+    cols: u16,
+    rows: u16,
+    /// None if the session was ended before the command exited
+    exit_code: Option<i32>,
+    /// Files written during the session, like the `--log-to-file` recording
+    files: Vec<String>,
+}
+
+impl Manifest {
+    fn new(
+        state: &AppState,
+        argv: Vec<String>,
+        started_at: SystemTime,
+        (cols, rows): (u16, u16),
+        files: Vec<String>,
+    ) -> Self {
+        let unix_ms = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis())
+        };
+        let dtos = state.all_dtos.blocking_lock();
+        let exit_code = dtos.iter().rev().find_map(|dto| match dto {
+            VteEventDto::SessionEnded { exit_code, .. } => Some(*exit_code),
+            _ => None,
+        });
+        let mut category_counts = BTreeMap::new();
+        for category in state.categories.blocking_lock().iter() {
+            *category_counts.entry(*category).or_default() += 1;
+        }
+        let byte_count = state
+            .raw_chunks
+            .blocking_lock()
+            .iter()
+            .map(|chunk| chunk.bytes.len())
+            .sum();
+        Self {
+            argv,
+            started_at_ms: unix_ms(started_at),
+            ended_at_ms: unix_ms(SystemTime::now()),
+            byte_count,
+            sequence_count: state.sequence_count.load(Ordering::Relaxed),
+            category_counts,
+            cols,
+            rows,
+            exit_code: exit_code.flatten(),
+            files,
+        }
+    }
+}
+
+/// Parse a whole recording up front, for the subcommands that don't need a web UI.
+/// Each sequence comes with the offset of its first byte in the file
+fn parse_file(file: &str) -> Result<Vec<(usize, Sequence)>> {
+    let bytes = read_recording(file)?;
+    let mut parser = Parser::new();
+    let mut pending_bytes = Vec::new();
+    let mut sequences = Vec::new();
+    for (i, byte) in bytes.iter().enumerate() {
+        parse_bytes(
+            &mut parser,
+            &mut pending_bytes,
+            &[*byte],
+            |actions, raw_bytes| sequences.push((i + 1 - raw_bytes.len(), (actions, raw_bytes))),
+        );
+    }
+    Ok(sequences)
+}
+
+/// A DTO from a recording, with the offset and category of the action it came from
+#[derive(Serialize)]
+struct ExplainedDto {
+    offset: usize,
+    category: &'static str,
+    /// Whether the action couldn't be decoded, for `check`
+    #[serde(skip)]
+    undecoded: bool,
+    #[serde(flatten)]
+    dto: VteEventDto,
+}
+
+/// Turn a recording into the same DTOs the web UI would show (minus the invisible line breaks)
+fn explain_file(file: &str) -> Result<Vec<ExplainedDto>> {
+    let mut builder = DtoBuilder::new(false, None, false);
+    let mut dtos = vec![];
+    // (offset, first action) of the sequence behind each DTO, so they're still correct after prints
+    // get merged together
+    let mut origins = vec![];
+    // a DCS string is only added once whatever follows it comes along, but it starts where its
+    // first sequence was
+    let mut dcs_start = None;
+    for (offset, (actions, raw_bytes)) in parse_file(file)? {
+        let first_action = actions[0].clone();
+        let len_before = dtos.len();
+        builder.append_sequence(&mut dtos, actions, raw_bytes);
+        if dtos.len() > len_before {
+            if let Some(start) = dcs_start.take() {
+                origins.resize(len_before + 1, start);
+            }
+        }
+        if builder.holding_dcs() {
+            dcs_start.get_or_insert((offset, first_action.clone()));
+        }
+        origins.resize(dtos.len(), (offset, first_action));
+    }
+    builder.finish_dcs(&mut dtos);
+    if let Some(start) = dcs_start {
+        origins.resize(dtos.len(), start);
+    }
+
+    Ok(dtos
+        .into_iter()
+        .zip(origins)
+        .filter(|(dto, _)| !matches!(dto, VteEventDto::InvisibleLineBreak {}))
+        .map(|(dto, (offset, action))| ExplainedDto {
+            offset,
+            category: action_category(&action),
+            undecoded: is_undecoded(&action, &dto),
+            dto,
+        })
+        .collect())
+}
+
+/// Write to stdout, treating the reader going away (e.g. piping into `head`) as a normal exit
+fn write_stdout(write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>) -> Result<()> {
+    let mut out = BufWriter::new(stdout().lock());
+    match write(&mut out).and_then(|_| out.flush()) {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn explain(file: &str, output_format: OutputFormat) -> Result<()> {
+    if let OutputFormat::ActionDebug = output_format {
+        let sequences = parse_file(file)?;
+        return write_stdout(|out| {
+            for (_, (actions, _)) in &sequences {
+                for action in actions {
+                    writeln!(out, "Action({action:?})")?;
+                }
+            }
+            Ok(())
+        });
+    }
+    let explained = explain_file(file)?;
+    write_stdout(|out| write_explained(out, &explained, output_format))
+}
+
+fn write_explained(
+    out: &mut dyn Write,
+    explained: &[ExplainedDto],
+    output_format: OutputFormat,
+) -> std::io::Result<()> {
+    match output_format {
+        OutputFormat::Text => {
+            for explained in explained {
+                if let Some(line) = explained.dto.explain() {
+                    writeln!(out, "{line}")?;
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let dtos: Vec<_> = explained.iter().map(|explained| &explained.dto).collect();
+            serde_json::to_writer_pretty(&mut *out, &dtos)?;
+            writeln!(out)?;
+        }
+        // explain prints the actions themselves, before they become DTOs
+        OutputFormat::ActionDebug => unreachable!(),
+        OutputFormat::Csv => {
+            writeln!(out, "offset,category,title,tooltip,raw_bytes")?;
+            for ExplainedDto {
+                offset,
+                category,
+                dto,
+                ..
+            } in explained
+            {
+                let (title, tooltip, raw_bytes) = dto.csv_fields();
+                writeln!(
+                    out,
+                    "{offset},{category},{},{},{}",
+                    csv_escape(title),
+                    csv_escape(&tooltip),
+                    csv_escape(raw_bytes)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode two recordings and line up their DTOs
+fn align_files(
+    a: &str,
+    b: &str,
+) -> Result<(Vec<ExplainedDto>, Vec<ExplainedDto>, Vec<diff::Edit>)> {
+    let a_dtos = explain_file(a)?;
+    let b_dtos = explain_file(b)?;
+    // offsets and cursor positions are bound to differ once anything has been inserted, so only
+    // compare the DTOs without them
+    let edits = diff::align(
+        &a_dtos
+            .iter()
+            .map(|e| e.dto.without_cursor())
+            .collect::<Vec<_>>(),
+        &b_dtos
+            .iter()
+            .map(|e| e.dto.without_cursor())
+            .collect::<Vec<_>>(),
+    );
+    Ok((a_dtos, b_dtos, edits))
+}
+
+fn diff_files(a: &str, b: &str, output_format: DiffFormat) -> Result<()> {
+    let (a_dtos, b_dtos, edits) = align_files(a, b)?;
+    let mismatches: Vec<_> = edits
+        .iter()
+        .filter(|edit| !matches!(edit, diff::Edit::Equal(..)))
+        .collect();
+
+    write_stdout(|out| match output_format {
+        DiffFormat::Text => {
+            if mismatches.is_empty() {
+                return writeln!(out, "No differences");
+            }
+            let (a_offset, b_offset) = divergence_offsets(&edits, &a_dtos, &b_dtos);
+            let describe = |offset: Option<usize>, file: &str| match offset {
+                Some(offset) => format!("byte {offset} of {file}"),
+                None => format!("the end of {file}"),
+            };
+            writeln!(
+                out,
+                "First divergence at {} and {}",
+                describe(a_offset, a),
+                describe(b_offset, b)
+            )?;
+            for edit in &mismatches {
+                let (sign, explained) = match edit {
+                    diff::Edit::Removed(i) => ('-', &a_dtos[*i]),
+                    diff::Edit::Added(j) => ('+', &b_dtos[*j]),
+                    diff::Edit::Equal(..) => unreachable!(),
+                };
+                let line = explained.dto.explain().unwrap_or_default();
+                writeln!(out, "{sign} @{} {line}", explained.offset)?;
+            }
+            Ok(())
+        }
+        DiffFormat::Json => {
+            let first_divergence = (!mismatches.is_empty()).then(|| {
+                let (a_offset, b_offset) = divergence_offsets(&edits, &a_dtos, &b_dtos);
+                serde_json::json!({ "a_offset": a_offset, "b_offset": b_offset })
+            });
+            let changes: Vec<_> = mismatches
+                .iter()
+                .map(|edit| match edit {
+                    diff::Edit::Removed(i) => {
+                        serde_json::json!({ "op": "removed", "event": a_dtos[*i] })
+                    }
+                    diff::Edit::Added(j) => {
+                        serde_json::json!({ "op": "added", "event": b_dtos[*j] })
+                    }
+                    diff::Edit::Equal(..) => unreachable!(),
+                })
+                .collect();
+            let diff = serde_json::json!({
+                "identical": mismatches.is_empty(),
+                "first_divergence": first_divergence,
+                "changes": changes,
+            });
+            serde_json::to_writer_pretty(&mut *out, &diff)?;
+            writeln!(out)
+        }
+    })?;
+
+    if !mismatches.is_empty() {
+        bail!("{a} and {b} differ");
+    }
+    Ok(())
+}
+
+/// Show two recordings in the web UI as one stream of events, with the ones only in A or only in B marked
+fn serve_diff(a: &str, b: &str, server: ServerArgs) -> Result<()> {
+    let (a_dtos, b_dtos, edits) = align_files(a, b)?;
+    let state = AppState::new(server.keepalive(), server.max_events as usize);
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    let mut last_was_line_break = false;
+    for edit in edits {
+        let dto = match edit {
+            diff::Edit::Equal(i, _) => a_dtos[i].dto.clone(),
+            diff::Edit::Removed(i) => VteEventDto::Diff {
+                side: DiffSide::Removed,
+                event: Box::new(a_dtos[i].dto.clone()),
+            },
+            diff::Edit::Added(j) => VteEventDto::Diff {
+                side: DiffSide::Added,
+                event: Box::new(b_dtos[j].dto.clone()),
+            },
+        };
+        // `explain_file` leaves out the invisible line breaks that lay the web UI out in lines, so put them back
+        let is_line_break = match &dto {
+            VteEventDto::Diff { event, .. } => matches!(**event, VteEventDto::LineBreak { .. }),
+            dto => matches!(dto, VteEventDto::LineBreak { .. }),
+        };
+        if is_line_break != last_was_line_break {
+            runtime.block_on(state.push_dto(VteEventDto::InvisibleLineBreak {}));
+        }
+        last_was_line_break = is_line_break;
+        runtime.block_on(state.push_dto(dto));
+    }
+
+    println!(
+        "{}{}{}{}{}{} 🎨",
+        "Comparing ".cyan(),
+        a.magenta(),
+        " and ".cyan(),
+        b.magenta(),
+        " in Escape Artist v".cyan(),
+        env!("CARGO_PKG_VERSION").cyan(),
+    );
+    println!(
+        "{}{}{}",
+        "Open ".cyan(),
+        server.url().magenta(),
+        " to view the differences, type CTRL+D to exit".cyan()
+    );
+
+    terminal::enable_raw_mode()?;
+    let _clean_up = CleanUp { raw_mode: true };
+    runtime.spawn(run_webserver(state, server));
+    wait_for_ctrl_d()
+}
+
+/// Where in each file the first mismatch happens. When something is missing from one file, that file's offset
+/// is where the missing event would have been (or None if it would have been at the very end)
+fn divergence_offsets(
+    edits: &[diff::Edit],
+    a_dtos: &[ExplainedDto],
+    b_dtos: &[ExplainedDto],
+) -> (Option<usize>, Option<usize>) {
+    let first = edits
+        .iter()
+        .position(|edit| !matches!(edit, diff::Edit::Equal(..)))
+        .unwrap_or(edits.len());
+    let a_offset = edits[first..].iter().find_map(|edit| match edit {
+        diff::Edit::Equal(i, _) | diff::Edit::Removed(i) => Some(a_dtos[*i].offset),
+        diff::Edit::Added(_) => None,
+    });
+    let b_offset = edits[first..].iter().find_map(|edit| match edit {
+        diff::Edit::Equal(_, j) | diff::Edit::Added(j) => Some(b_dtos[*j].offset),
+        diff::Edit::Removed(_) => None,
+    });
+    (a_offset, b_offset)
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn play(file: &str, line_delay: Duration) -> Result<()> {
+    let bytes = read_recording(file)?;
+    if line_delay.is_zero() {
+        return write_stdout(|out| out.write_all(&bytes));
+    }
+
+    write_stdout(|out| {
+        for line in bytes.split_inclusive(|b| *b == b'\n') {
+            out.write_all(line)?;
+            out.flush()?;
+            thread::sleep(line_delay);
+        }
+        Ok(())
+    })
+}
+
+fn check(file: &str) -> Result<()> {
+    // decoded like `explain` does, so DCS strings are judged as a whole rather than byte by byte
+    let mut undecoded = 0;
+    for explained in explain_file(file)? {
+        if explained.undecoded {
+            undecoded += 1;
+            if let Some(line) = explained.dto.explain() {
+                println!("{line}");
+            }
+        }
+    }
+
+    if undecoded > 0 {
+        bail!("{undecoded} escape sequences in {file} could not be decoded");
+    }
+    println!("All escape sequences in {file} were decoded");
+    Ok(())
+}
+
+async fn run_webserver(cloned_state: AppState, server: ServerArgs) {
     let app = Router::new()
         .route("/", get(root))
         .route("/events", get(events_websocket))
+        .route("/bytes", get(bytes_websocket))
+        .route("/timeline", get(timeline))
+        .route("/activity", get(activity))
+        .route("/stats", get(stats))
+        .route("/screen", get(screen))
+        .route("/static-view", get(static_view))
+        .route("/export", get(export_html))
+        .route("/annotations", get(annotations))
+        .route("/raw-reconstructed", get(raw_reconstructed))
+        .route("/clear", post(clear))
         .route("/*file", get(static_handler))
         .with_state(cloned_state);
-    let url = format!("http://localhost:{}", cli.port);
-    let _ = open::that(url);
-    let addr = SocketAddr::from(([127, 0, 0, 1], cli.port));
+    let _ = open::that(server.url());
+    let addr = SocketAddr::from((server.host, server.port));
     let listener = TcpListener::bind(addr)
         .await
         .expect("Failed to bind to socket. Maybe another service is already using the same port");
@@ -266,11 +1196,16 @@ async fn run_webserver(cloned_state: AppState, cli: Cli) {
         .expect("Failed to start HTTP server.");
 }
 
+/// Parse everything `reader` produces and send the actions to `action_sender`. With `follow`, the end of
+/// the output is only where it ends for now, and we keep checking for more
 fn parse_raw_output(
     log_to_file: bool,
+    mut cast_writer: Option<CastWriter>,
     write_to_stdout: bool,
     mut reader: Box<dyn Read + Send>,
-    action_sender: Sender<(Action, Vec<u8>)>,
+    follow: bool,
+    action_sender: Sender<Sequence>,
+    state: AppState,
 ) -> Result<()> {
     let mut parser = Parser::new();
     let mut recording = if log_to_file {
@@ -282,21 +1217,34 @@ fn parse_raw_output(
     let mut curr_cmd_bytes = Vec::new();
     loop {
         let size = reader.read(&mut buf)?;
-        let bytes = buf[0..size].to_vec();
-
-        for byte in &bytes {
-            curr_cmd_bytes.push(*byte);
-
-            let actions = parser.parse_as_vec(&[*byte]);
-            if !actions.is_empty() {
-                // 1 byte sequence can represent multiple actions
-                let cmd_bytes = take(&mut curr_cmd_bytes);
-                for action in actions {
-                    // this may fail if the receiver has been dropped because we're exiting
-                    let _ = action_sender.blocking_send((action, cmd_bytes.clone()));
-                }
+        if size == 0 && follow {
+            // nothing new has been written yet
+            if action_sender.is_closed() {
+                return Ok(());
             }
+            thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+        if size == 0 {
+            info!("reached the end of the output");
+            return Ok(());
         }
+        trace!("read {size} bytes");
+        let bytes = buf[0..size].to_vec();
+        state.record_chunk(&bytes);
+
+        parse_bytes(
+            &mut parser,
+            &mut curr_cmd_bytes,
+            &bytes,
+            |actions, cmd_bytes| {
+                trace!("parsed {actions:?} from {cmd_bytes:?}");
+                // this may fail if the receiver has been dropped because we're exiting
+                if action_sender.blocking_send((actions, cmd_bytes)).is_err() {
+                    debug!("action receiver dropped, discarding actions");
+                }
+            },
+        );
 
         if write_to_stdout {
             stdout().write_all(&bytes)?;
@@ -306,60 +1254,277 @@ fn parse_raw_output(
         if let Some(recording) = &mut recording {
             recording.write_all(&bytes)?;
         }
+
+        if let Some(cast_writer) = &mut cast_writer {
+            cast_writer.write_output(&bytes)?;
+        }
     }
 }
 
-async fn process_actions(mut action_receiver: Receiver<(Action, Vec<u8>)>, state: AppState) {
-    let mut fg_color = ColorSpec::Default;
-    let mut bg_color = ColorSpec::Default;
-    let mut last_was_line_break = false;
-    while let Some((action, raw_bytes)) = action_receiver.recv().await {
-        // optimization: if the last DTO was a print and this action is a print, concatenate them
-        // this greatly cuts down on the number of events sent to the front-end
-        if let Some(VteEventDto::Print {
-            string: last_string,
-            ..
-        }) = state.all_dtos.lock().await.last_mut()
-        {
-            if let Action::Print(c) = &action {
-                last_string.push(*c);
-                let tuple = (action, raw_bytes);
-                let dto = VteEventDto::from(&tuple);
-                let _ = state.tx.send(dto);
-                continue;
+/// Parse a recording and send its actions to `action_sender`, in one go if `text` is set and it's valid UTF-8.
+/// With a `speed`, an asciinema recording is parsed at the pace it was recorded instead. With `follow`,
+/// whatever gets appended to the file later is parsed too
+fn parse_recording(
+    file: &str,
+    text: bool,
+    speed: Option<f64>,
+    follow: bool,
+    action_sender: Sender<Sequence>,
+    state: AppState,
+) -> Result<()> {
+    if text {
+        match String::from_utf8(read_recording(file)?) {
+            Ok(text) => {
+                state.record_chunk(text.as_bytes());
+                parse_text(&text, action_sender);
+                return Ok(());
             }
-        } else {
-            state.sequence_count.fetch_add(1, Ordering::Relaxed);
+            Err(e) => warn!("{file} is not valid UTF-8 ({e}), parsing it byte by byte"),
         }
+    }
+    let reader = open_recording(file, speed)?;
+    parse_raw_output(false, None, false, reader, follow, action_sender, state)
+}
 
-        // otherwise, carry on; update global colours if needed and add the event to the list
+/// Everything in a recording, which is either a program's output as-is (like `--log-to-file` writes) or
+/// an asciinema recording
+fn read_recording(file: &str) -> Result<Vec<u8>> {
+    let mut contents = vec![];
+    open_recording(file, None)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
 
-        update_global_colors(&action, &mut fg_color, &mut bg_color);
-        let tuple = (action, raw_bytes);
-        let mut dto = VteEventDto::from(&tuple);
-        update_print_colors(&mut dto, fg_color, bg_color);
+/// Like `read_recording`, but a program's output as-is gets streamed from the file. With a `speed`, an
+/// asciinema recording is read at the pace it was recorded. Gzipped files are decompressed as they're read
+fn open_recording(file: &str, speed: Option<f64>) -> Result<Box<dyn Read + Send>> {
+    // peek at the start instead of opening the file twice, since it could be a pipe
+    let mut reader = BufReader::with_capacity(64 * 1024, File::open(file)?);
+    let gzipped = file.ends_with(".gz") || reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    let mut reader: Box<dyn BufRead + Send> = if gzipped {
+        debug!("decompressing {file} as gzip");
+        Box::new(BufReader::with_capacity(
+            64 * 1024,
+            MultiGzDecoder::new(reader),
+        ))
+    } else {
+        Box::new(reader)
+    };
+    // so that e.g. demo.cast.gz is recognized by its extension
+    let name = file.strip_suffix(".gz").unwrap_or(file);
+    if !cast::is_cast(name, reader.fill_buf()?) {
+        return Ok(Box::new(reader));
+    }
+    let mut contents = vec![];
+    reader.read_to_end(&mut contents)?;
+    let events = cast::parse_output_events(file, &contents)?;
+    Ok(match speed {
+        Some(speed) => Box::new(TimedReader::new(events, speed)),
+        None => Box::new(Cursor::new(
+            events
+                .into_iter()
+                .flat_map(|(_, data)| data)
+                .collect::<Vec<u8>>(),
+        )),
+    })
+}
 
-        // emit an invisible line break DTO if we're transitioning from a line break to a non-line break or vice versa
-        let is_line_break = matches!(&dto, VteEventDto::LineBreak { .. });
-        let dtos_to_send = if is_line_break && !last_was_line_break {
-            vec![VteEventDto::InvisibleLineBreak {}, dto]
-        } else if !is_line_break && last_was_line_break {
-            vec![VteEventDto::InvisibleLineBreak {}, dto]
-        } else {
-            vec![dto]
-        };
-        last_was_line_break = is_line_break;
+/// The first two bytes of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
+fn modified_time(file: &str) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Block until `file`'s modification time is something other than `since`.
+/// Polling is plenty for a file that gets rewritten every few seconds at most
+fn wait_for_change(file: &str, since: Option<SystemTime>) {
+    loop {
+        thread::sleep(Duration::from_millis(250));
+        let modified = modified_time(file);
+        // a missing file is probably in the middle of being replaced
+        if modified.is_some() && modified != since {
+            return;
+        }
+    }
+}
+
+/// Parse all of `text` in a single call. That doesn't tell us which bytes produced which action,
+/// so each action's raw bytes are re-encoded from the action itself
+fn parse_text(text: &str, action_sender: Sender<Sequence>) {
+    let actions = Parser::new().parse_as_vec(text.as_bytes());
+    info!(
+        "parsed {} actions from {} bytes of text",
+        actions.len(),
+        text.len()
+    );
+    for action in actions {
+        let raw_bytes = action.to_string().into_bytes();
+        if action_sender
+            .blocking_send((vec![action], raw_bytes))
+            .is_err()
         {
+            debug!("action receiver dropped, discarding actions");
+            return;
+        }
+    }
+}
+
+/// Turn sequences into DTOs and send them to clients. With `playback_controls`, clients can pause
+/// and step through the sequences; live sessions can't be held up like that, so they don't get it
+async fn process_actions(
+    mut action_receiver: Receiver<Sequence>,
+    state: AppState,
+    mut builder: DtoBuilder,
+    head: Option<usize>,
+    explain_json: bool,
+    playback_controls: bool,
+) {
+    let mut truncated = false;
+    // the bytes of cursor movements that the builder is holding back for --summarize-cursor
+    let mut held_raw_bytes = vec![];
+    let mut held_category = "CSI";
+    let mut playback = playback_controls.then(|| state.playback.subscribe());
+    while let Some((actions, raw_bytes)) = action_receiver.recv().await {
+        // keep receiving after hitting the --head limit, so whatever is sending doesn't block
+        if truncated {
+            continue;
+        }
+        if let Some(playback) = &mut playback {
+            state.wait_for_playback(playback).await;
+        }
+        let ts_ms;
+        let (dtos_to_send, index, first_seq) = {
             let mut dtos = state.all_dtos.lock().await;
-            for dto in dtos_to_send.iter() {
-                dtos.push(dto.clone());
+            // taken with `all_dtos` locked, like `push_dto` does, so later events never have earlier
+            // timestamps
+            ts_ms = state.start.elapsed().as_millis() as u64;
+            if !matches!(dtos.last(), Some(VteEventDto::Print { .. })) {
+                if let Some(limit) = head {
+                    if state.sequence_count.load(Ordering::Relaxed) >= limit as i64 {
+                        truncated = true;
+                        drop(dtos);
+                        state.push_dto(VteEventDto::Truncated { limit }).await;
+                        continue;
+                    }
+                }
+                state.sequence_count.fetch_add(1, Ordering::Relaxed);
+            }
+            let len_before = dtos.len();
+            let mut tallies = state.tallies.lock().await;
+            let mut screen = state.screen.lock().await;
+            for action in &actions {
+                tallies.record(action);
+                screen.apply(action);
+            }
+            drop(screen);
+            drop(tallies);
+            let category = action_category(&actions[0]);
+            let dtos_to_send = builder.append_sequence(&mut dtos, actions, raw_bytes.clone());
+            // the DTOs to send are the newest ones, or fragments merged into the newest one
+            let first_seq = state.seq(dtos.len().saturating_sub(dtos_to_send.len()));
+            // a finished run of cursor movements or DCS string comes before this sequence's own DTOs
+            let mut first_new = len_before;
+            if !held_raw_bytes.is_empty() && dtos.len() > len_before {
+                state
+                    .categories
+                    .lock()
+                    .await
+                    .resize(len_before + 1, held_category);
+                let mut all_raw_bytes = state.raw_bytes.lock().await;
+                all_raw_bytes.resize(len_before + 1, vec![]);
+                all_raw_bytes[len_before].extend(take(&mut held_raw_bytes));
+                first_new += 1;
+            }
+
+            // merged prints keep the timestamp of the first print
+            state.timestamps_ms.lock().await.resize(dtos.len(), ts_ms);
+            state.categories.lock().await.resize(dtos.len(), category);
+            let mut all_raw_bytes = state.raw_bytes.lock().await;
+            all_raw_bytes.resize(dtos.len(), vec![]);
+            if builder.holding_cursor_run() || builder.holding_dcs() {
+                held_raw_bytes.extend(raw_bytes);
+                held_category = if builder.holding_dcs() { "DCS" } else { "CSI" };
+                (dtos_to_send, None, first_seq)
+            } else {
+                // the sequence's bytes go with the first DTO it produced, or the one it was merged into
+                let index = first_new.min(dtos.len() - 1);
+                all_raw_bytes[index].extend(raw_bytes);
+                drop(all_raw_bytes);
+                let evicted = state.evict_oldest(&mut dtos).await;
+                (dtos_to_send, index.checked_sub(evicted), first_seq)
+            }
+        };
+
+        for (dto, seq) in dtos_to_send.into_iter().zip(first_seq..) {
+            let dto = TimedDto::new(dto, ts_ms, seq);
+            if explain_json {
+                write_json_line(&dto);
+            }
+            // this fails when no clients are connected, which is fine; they'll get all_dtos when they connect
+            if state.tx.send(dto).is_err() {
+                trace!("no websocket clients connected");
             }
         }
 
-        for dto in dtos_to_send {
-            let _ = state.tx.send(dto);
+        // only the first output after an input says anything about latency
+        let Some(index) = index else {
+            continue;
+        };
+        let input_ms = state.last_input_ms.swap(-1, Ordering::Relaxed);
+        if input_ms >= 0 {
+            let latency_ms = state.start.elapsed().as_millis() as i64 - input_ms;
+            state
+                .annotate(
+                    index,
+                    format!("Output {latency_ms} ms after the last input"),
+                )
+                .await;
+        }
+    }
+    debug!("action channel closed, no more actions to process");
+
+    // a recording can end in the middle of a run of cursor movements or a DCS string
+    let mut dtos = state.all_dtos.lock().await;
+    let mut dtos_to_send = builder.finish_dcs(&mut dtos);
+    dtos_to_send.extend(builder.finish_cursor_run(&mut dtos));
+    if dtos_to_send.is_empty() {
+        return;
+    }
+    let ts_ms = state.start.elapsed().as_millis() as u64;
+    let first_seq = state.seq(dtos.len().saturating_sub(dtos_to_send.len()));
+    state.timestamps_ms.lock().await.resize(dtos.len(), ts_ms);
+    state
+        .categories
+        .lock()
+        .await
+        .resize(dtos.len(), held_category);
+    let mut all_raw_bytes = state.raw_bytes.lock().await;
+    all_raw_bytes.resize(dtos.len(), vec![]);
+    all_raw_bytes[dtos.len() - 1].extend(held_raw_bytes);
+    drop(all_raw_bytes);
+    state.evict_oldest(&mut dtos).await;
+    for (dto, seq) in dtos_to_send.into_iter().zip(first_seq..) {
+        let dto = TimedDto::new(dto, ts_ms, seq);
+        if explain_json {
+            write_json_line(&dto);
         }
+        let _ = state.tx.send(dto);
+    }
+}
+
+/// Write an event to stdout for `--explain-json`, flushing it straight away so whatever is reading can
+/// act on it. Invisible line breaks are left out, like with `explain`
+fn write_json_line(dto: &TimedDto) {
+    if matches!(dto.dto, VteEventDto::InvisibleLineBreak {}) {
+        return;
+    }
+    let mut out = stdout().lock();
+    let written = serde_json::to_writer(&mut out, dto)
+        .map_err(std::io::Error::from)
+        .and_then(|_| writeln!(out))
+        .and_then(|_| out.flush());
+    if let Err(e) = written {
+        warn!("failed to write an event to stdout: {e}");
     }
 }
 
@@ -367,17 +1532,251 @@ fn initialize_environment() {
     std::env::set_var("RUST_BACKTRACE", "1");
     let default_panic = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
-        terminal::disable_raw_mode().expect("Could not disable raw mode");
-        execute!(stdout(), cursor::SetCursorStyle::DefaultUserShape).unwrap();
+        // best effort; these fail when there's no terminal, e.g. when explain's output is piped somewhere
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(stdout(), cursor::SetCursorStyle::DefaultUserShape);
         default_panic(info);
     }));
 }
 
+/// A DTO on its way to websocket clients, with when it was received (ms since `AppState::start`) and
+/// its sequence number if it's one of `all_dtos`
+#[derive(Debug, Serialize, Clone)]
+struct TimedDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts_ms: Option<u64>,
+    /// Counts up from 0 for the whole session, unaffected by eviction or `/clear`, so a reconnecting
+    /// client can ask for just the events it hasn't seen with `?since=`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+    #[serde(flatten)]
+    dto: VteEventDto,
+}
+
+impl TimedDto {
+    fn new(dto: VteEventDto, ts_ms: u64, seq: u64) -> Self {
+        Self {
+            ts_ms: Some(ts_ms),
+            seq: Some(seq),
+            dto,
+        }
+    }
+}
+
+impl From<VteEventDto> for TimedDto {
+    fn from(dto: VteEventDto) -> Self {
+        Self {
+            ts_ms: None,
+            seq: None,
+            dto,
+        }
+    }
+}
+
 #[derive(Clone)]
 struct AppState {
     sequence_count: Arc<AtomicI64>,
+    /// The most recent events, at most `max_events` of them
     all_dtos: Arc<Mutex<Vec<VteEventDto>>>,
-    tx: broadcast::Sender<VteEventDto>,
+    max_events: usize,
+    /// How many of the oldest events have been dropped from `all_dtos` to stay under `max_events`
+    dropped_count: Arc<AtomicUsize>,
+    /// The sequence number of the first event in `all_dtos`. Unlike `dropped_count`, this keeps
+    /// counting through `clear`
+    first_seq: Arc<AtomicU64>,
+    /// When each DTO in `all_dtos` was received, in ms since `start`. Always lock `all_dtos` first
+    timestamps_ms: Arc<Mutex<Vec<u64>>>,
+    /// The `action_category` of the sequence behind each DTO in `all_dtos`. Always lock `all_dtos` first
+    categories: Arc<Mutex<Vec<&'static str>>>,
+    /// The unmodified bytes behind each DTO in `all_dtos`. Always lock `all_dtos` first
+    raw_bytes: Arc<Mutex<Vec<Vec<u8>>>>,
+    /// Always lock `all_dtos` first
+    tallies: Arc<Mutex<Tallies>>,
+    /// What the terminal would be showing after every sequence so far. Always lock `all_dtos` first
+    screen: Arc<Mutex<VirtualScreen>>,
+    /// Notes that clients attached to DTOs, keyed by index in `all_dtos`. Always lock `all_dtos` first
+    annotations: Arc<Mutex<BTreeMap<usize, String>>>,
+    /// Every chunk of output as it was read, before parsing
+    raw_chunks: Arc<Mutex<Vec<RawChunk>>>,
+    chunk_tx: broadcast::Sender<RawChunk>,
+    start: Instant,
+    /// When `--input-latency` last saw input, in ms since `start`, or -1 once output after it has been
+    /// noted
+    last_input_ms: Arc<AtomicI64>,
+    /// How long a websocket can go without traffic before we ping it
+    keepalive: Option<Duration>,
+    tx: broadcast::Sender<TimedDto>,
+    /// Whether a replay is running, paused or stepping, as set by websocket clients
+    playback: Arc<watch::Sender<Playback>>,
+}
+
+/// How `process_actions` feeds a replay's sequences through, so it can be stepped through like a debugger
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Playback {
+    Playing,
+    Paused,
+    /// Let this many more sequences through, then pause
+    Stepping(usize),
+    /// Let sequences through until the event at this index in `all_dtos` exists, then pause
+    RunningTo(usize),
+}
+
+impl AppState {
+    fn new(keepalive: Option<Duration>, max_events: usize) -> Self {
+        let (tx, _) = broadcast::channel::<TimedDto>(10000); // capacity arbitrarily chosen
+        let (chunk_tx, _) = broadcast::channel::<RawChunk>(1000);
+        Self {
+            sequence_count: Arc::new(AtomicI64::new(0)),
+            all_dtos: Arc::new(Mutex::new(vec![])),
+            max_events,
+            dropped_count: Arc::new(AtomicUsize::new(0)),
+            first_seq: Arc::new(AtomicU64::new(0)),
+            timestamps_ms: Arc::new(Mutex::new(vec![])),
+            categories: Arc::new(Mutex::new(vec![])),
+            raw_bytes: Arc::new(Mutex::new(vec![])),
+            tallies: Arc::new(Mutex::new(Tallies::default())),
+            screen: Arc::new(Mutex::new(VirtualScreen::default())),
+            annotations: Arc::new(Mutex::new(BTreeMap::new())),
+            raw_chunks: Arc::new(Mutex::new(vec![])),
+            chunk_tx,
+            start: Instant::now(),
+            last_input_ms: Arc::new(AtomicI64::new(-1)),
+            keepalive,
+            tx,
+            playback: Arc::new(watch::channel(Playback::Playing).0),
+        }
+    }
+
+    /// Wait until the playback controls let another sequence through, counting it against a step
+    async fn wait_for_playback(&self, playback: &mut watch::Receiver<Playback>) {
+        loop {
+            let current = *playback.borrow_and_update();
+            let next = match current {
+                Playback::Playing => return,
+                Playback::Paused => None,
+                Playback::Stepping(0) => Some(Playback::Paused),
+                Playback::Stepping(steps) => {
+                    self.playback.send_replace(Playback::Stepping(steps - 1));
+                    return;
+                }
+                Playback::RunningTo(index) if self.all_dtos.lock().await.len() <= index => return,
+                Playback::RunningTo(_) => Some(Playback::Paused),
+            };
+            if let Some(next) = next {
+                self.playback.send_replace(next);
+                continue;
+            }
+            // the sender lives in `self`, so this can't fail
+            let _ = playback.changed().await;
+        }
+    }
+
+    /// Forget all the DTOs, e.g. because the file being replayed changed, and tell clients to do the same
+    async fn clear(&self) {
+        let mut dtos = self.all_dtos.lock().await;
+        self.first_seq
+            .fetch_add(dtos.len() as u64, Ordering::Relaxed);
+        dtos.clear();
+        self.timestamps_ms.lock().await.clear();
+        self.categories.lock().await.clear();
+        self.raw_bytes.lock().await.clear();
+        *self.tallies.lock().await = Tallies::default();
+        self.annotations.lock().await.clear();
+        self.raw_chunks.lock().await.clear();
+        let mut screen = self.screen.lock().await;
+        let (cols, rows) = screen.size();
+        *screen = VirtualScreen::new(cols, rows);
+        drop(screen);
+        self.sequence_count.store(0, Ordering::Relaxed);
+        self.dropped_count.store(0, Ordering::Relaxed);
+        let _ = self.tx.send(VteEventDto::Clear {}.into());
+    }
+
+    /// Drop the oldest events (and everything stored alongside them) once there are more than
+    /// `max_events`. Takes `all_dtos` already locked, and returns how many were dropped
+    async fn evict_oldest(&self, dtos: &mut Vec<VteEventDto>) -> usize {
+        if dtos.len() <= self.max_events {
+            return 0;
+        }
+        // go a tenth under the limit so we're not shifting everything along for every new event.
+        // The newest event always stays, so prints can still be merged into it
+        let keep = (self.max_events - self.max_events / 10).max(1);
+        let count = dtos.len() - keep;
+        dtos.drain(..count);
+        self.timestamps_ms.lock().await.drain(..count);
+        self.categories.lock().await.drain(..count);
+        self.raw_bytes.lock().await.drain(..count);
+        let mut annotations = self.annotations.lock().await;
+        *annotations = take(&mut *annotations)
+            .into_iter()
+            .filter_map(|(id, note)| Some((id.checked_sub(count)?, note)))
+            .collect();
+        self.dropped_count.fetch_add(count, Ordering::Relaxed);
+        self.first_seq.fetch_add(count as u64, Ordering::Relaxed);
+        debug!("dropped the oldest {count} events");
+        count
+    }
+
+    /// The sequence number of the event at `index` in `all_dtos`. Only call this with `all_dtos` locked,
+    /// so nothing can be evicted in between
+    fn seq(&self, index: usize) -> u64 {
+        self.first_seq.load(Ordering::Relaxed) + index as u64
+    }
+
+    /// Stands in for the events dropped by `evict_oldest`, if there were any
+    fn dropped_marker(&self) -> Option<VteEventDto> {
+        let count = self.dropped_count.load(Ordering::Relaxed);
+        (count > 0).then_some(VteEventDto::EventsDropped { count })
+    }
+
+    /// Store a note on a DTO and send it to all clients, including the one that wrote it
+    async fn annotate(&self, id: usize, note: String) {
+        let dtos = self.all_dtos.lock().await;
+        if id >= dtos.len() {
+            warn!(
+                "ignoring annotation for event {id}, there are only {} events",
+                dtos.len()
+            );
+            return;
+        }
+        self.annotations.lock().await.insert(id, note.clone());
+        let _ = self.tx.send(
+            VteEventDto::Annotation {
+                target_id: id,
+                note,
+            }
+            .into(),
+        );
+    }
+
+    /// Store a chunk of output as it was read and send it to `/bytes` clients. Only call this from
+    /// blocking threads, like the ones reading the output
+    fn record_chunk(&self, bytes: &[u8]) {
+        let mut chunks = self.raw_chunks.blocking_lock();
+        let offset = chunks
+            .last()
+            .map_or(0, |last| last.offset + last.bytes.len());
+        let chunk = RawChunk {
+            offset,
+            bytes: bytes.to_vec(),
+            timestamp_ms: self.start.elapsed().as_millis() as u64,
+        };
+        chunks.push(chunk.clone());
+        let _ = self.chunk_tx.send(chunk);
+    }
+
+    /// Store a DTO that didn't come from the child's output and send it to connected clients
+    async fn push_dto(&self, dto: VteEventDto) {
+        let mut dtos = self.all_dtos.lock().await;
+        dtos.push(dto.clone());
+        let ts_ms = self.start.elapsed().as_millis() as u64;
+        let seq = self.seq(dtos.len() - 1);
+        self.timestamps_ms.lock().await.resize(dtos.len(), ts_ms);
+        self.categories.lock().await.resize(dtos.len(), "Session");
+        self.raw_bytes.lock().await.resize(dtos.len(), vec![]);
+        self.evict_oldest(&mut dtos).await;
+        let _ = self.tx.send(TimedDto::new(dto, ts_ms, seq));
+    }
 }
 
 #[axum::debug_handler]
@@ -395,375 +1794,819 @@ async fn static_handler(uri: Uri) -> impl IntoResponse {
 #[folder = "embed/"]
 struct Asset;
 
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// Send icon keys instead of inline SVGs, and omit empty fields
+    #[serde(default)]
+    minimal: bool,
+    /// Comma-separated event types not to send, like `Print,LineBreak`
+    exclude: Option<String>,
+    /// Comma-separated event types to send, leaving out all the others
+    only: Option<String>,
+    /// The `seq` of the newest event a reconnecting client already has, so it's only sent the ones
+    /// after it
+    since: Option<u64>,
+}
+
+/// Which events a websocket client wants, going by the `type` tag they're serialized with. Names
+/// that aren't an event type never match anything, so they're effectively ignored
+#[derive(Default)]
+struct EventFilter {
+    only: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+}
+
+impl EventFilter {
+    fn new(only: Option<&str>, exclude: Option<&str>) -> Self {
+        let names = |list: &str| -> HashSet<String> {
+            list.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        Self {
+            only: only.map(names),
+            exclude: exclude.map(names).unwrap_or_default(),
+        }
+    }
+
+    fn allows(&self, dto: &VteEventDto) -> bool {
+        // clients rely on `Clear` to know when everything is about to be resent
+        if matches!(dto, VteEventDto::Clear {}) || (self.only.is_none() && self.exclude.is_empty())
+        {
+            return true;
+        }
+        let value = serde_json::to_value(dto).unwrap();
+        let kind = value
+            .get("type")
+            .and_then(|kind| kind.as_str())
+            .unwrap_or("");
+        self.only.as_ref().is_none_or(|only| only.contains(kind)) && !self.exclude.contains(kind)
+    }
+}
+
+#[derive(Serialize)]
+struct Timeline {
+    duration_ms: u64,
+    event_count: usize,
+    first_ts: Option<u64>,
+    last_ts: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ActivityQuery {
+    buckets: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Activity {
+    /// The timestamp the first bucket starts at, in ms since escape-artist started
+    first_ts: Option<u64>,
+    bucket_ms: u64,
+    /// How many events of each `action_category` happened in each slice of the session
+    buckets: Vec<BTreeMap<&'static str, usize>>,
+}
+
+#[derive(Serialize)]
+struct ColorCount {
+    color: String,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    sequence_count: i64,
+    event_count: usize,
+    top_foreground_colors: Vec<ColorCount>,
+    top_background_colors: Vec<ColorCount>,
+    reset_count: usize,
+    resets: BTreeMap<&'static str, usize>,
+    printable_chars: usize,
+    sgr_changes: usize,
+    cursor_moves: usize,
+    erases: usize,
+    osc_commands: usize,
+    control_codes: usize,
+}
+
+/// Running counts for `/stats`
+#[derive(Default)]
+struct Tallies {
+    /// How many times each color (as hex) was set with SGR, not counting resets to the default
+    foreground: HashMap<String, usize>,
+    background: HashMap<String, usize>,
+    /// Keyed by `reset_kind`
+    resets: BTreeMap<&'static str, usize>,
+    /// How chatty the program is, by kind of sequence. Counted as actions are decoded, so they cover
+    /// the whole session even after `--max-events` drops old events
+    printable_chars: usize,
+    sgr_changes: usize,
+    cursor_moves: usize,
+    erases: usize,
+    osc_commands: usize,
+    control_codes: usize,
+}
+
+impl Tallies {
+    fn record(&mut self, action: &Action) {
+        if let Some(kind) = reset_kind(action) {
+            *self.resets.entry(kind).or_default() += 1;
+        }
+        match action {
+            Action::Print(_) => self.printable_chars += 1,
+            Action::PrintString(s) => self.printable_chars += s.chars().count(),
+            Action::Control(_) => self.control_codes += 1,
+            Action::OperatingSystemCommand(_) => self.osc_commands += 1,
+            Action::CSI(CSI::Sgr(_)) => self.sgr_changes += 1,
+            Action::CSI(CSI::Edit(
+                Edit::EraseInDisplay(_) | Edit::EraseInLine(_) | Edit::EraseCharacter(_),
+            )) => self.erases += 1,
+            _ if is_cursor_move(action) => self.cursor_moves += 1,
+            _ => {}
+        }
+        let (counts, color) = match action {
+            Action::CSI(CSI::Sgr(Sgr::Foreground(color))) => (&mut self.foreground, color),
+            Action::CSI(CSI::Sgr(Sgr::Background(color))) => (&mut self.background, color),
+            _ => return,
+        };
+        if let Some(hex) = hex_color(color) {
+            *counts.entry(hex).or_default() += 1;
+        }
+    }
+}
+
+/// The most used colors first
+fn top_colors(counts: &HashMap<String, usize>) -> Vec<ColorCount> {
+    let mut top: Vec<_> = counts
+        .iter()
+        .map(|(color, count)| ColorCount {
+            color: color.clone(),
+            count: *count,
+        })
+        .collect();
+    top.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.color.cmp(&b.color)));
+    top.truncate(10);
+    top
+}
+
+/// Summary numbers for the session so far, including which colors the program uses most, how
+/// often it resets the terminal and how many of each kind of sequence it sent
+#[axum::debug_handler]
+async fn stats(State(state): State<AppState>) -> impl IntoResponse {
+    let event_count = state.all_dtos.lock().await.len();
+    let tallies = state.tallies.lock().await;
+    Json(Stats {
+        sequence_count: state.sequence_count.load(Ordering::Relaxed),
+        event_count,
+        top_foreground_colors: top_colors(&tallies.foreground),
+        top_background_colors: top_colors(&tallies.background),
+        reset_count: tallies.resets.values().sum(),
+        resets: tallies.resets.clone(),
+        printable_chars: tallies.printable_chars,
+        sgr_changes: tallies.sgr_changes,
+        cursor_moves: tallies.cursor_moves,
+        erases: tallies.erases,
+        osc_commands: tallies.osc_commands,
+        control_codes: tallies.control_codes,
+    })
+}
+
+/// What the terminal would be showing now: every row of the virtual screen as runs of styled text, plus
+/// where the cursor is
+#[axum::debug_handler]
+async fn screen(State(state): State<AppState>) -> impl IntoResponse {
+    let _dtos = state.all_dtos.lock().await;
+    Json(state.screen.lock().await.snapshot())
+}
+
+/// The time span of the session so far, for building a scrubber UI. Timestamps are ms since escape-artist started
+#[axum::debug_handler]
+async fn timeline(State(state): State<AppState>) -> impl IntoResponse {
+    let dtos = state.all_dtos.lock().await;
+    let timestamps = state.timestamps_ms.lock().await;
+    let first_ts = timestamps.first().copied();
+    let last_ts = timestamps.last().copied();
+    let duration_ms = match (timestamps.iter().min(), timestamps.iter().max()) {
+        (Some(min), Some(max)) => max - min,
+        _ => 0,
+    };
+    Json(Timeline {
+        duration_ms,
+        event_count: dtos.len(),
+        first_ts,
+        last_ts,
+    })
+}
+
+/// A histogram of events per category over the session so far, split into `?buckets=N` equal slices of
+/// time (default 50), for drawing an overview that shows where the bursts of activity are
+#[axum::debug_handler]
+async fn activity(
+    State(state): State<AppState>,
+    Query(query): Query<ActivityQuery>,
+) -> impl IntoResponse {
+    let bucket_count = query.buckets.unwrap_or(50).clamp(1, 1000);
+    let _dtos = state.all_dtos.lock().await;
+    let timestamps = state.timestamps_ms.lock().await;
+    let categories = state.categories.lock().await;
+
+    let first_ts = timestamps.first().copied();
+    let span_ms = timestamps
+        .last()
+        .copied()
+        .unwrap_or(0)
+        .saturating_sub(first_ts.unwrap_or(0));
+    // +1 so the last event lands in the last bucket instead of one past it
+    let bucket_ms = (span_ms + 1).div_ceil(bucket_count as u64);
+    let mut buckets = vec![BTreeMap::new(); bucket_count];
+    for (ts, category) in timestamps.iter().zip(categories.iter()) {
+        let index = (ts.saturating_sub(first_ts.unwrap_or(0)) / bucket_ms) as usize;
+        *buckets[index.min(bucket_count - 1)]
+            .entry(*category)
+            .or_default() += 1;
+    }
+    Json(Activity {
+        first_ts,
+        bucket_ms,
+        buckets,
+    })
+}
+
+/// Every note clients have attached to events, keyed by the event's index
+#[axum::debug_handler]
+async fn annotations(State(state): State<AppState>) -> impl IntoResponse {
+    let _dtos = state.all_dtos.lock().await;
+    Json(state.annotations.lock().await.clone())
+}
+
+/// A snapshot of the session as a plain HTML page, for when the live UI's JavaScript isn't an option
+#[axum::debug_handler]
+async fn static_view(State(state): State<AppState>) -> impl IntoResponse {
+    let dtos = state.all_dtos.lock().await;
+    match state.dropped_marker() {
+        Some(marker) => {
+            let events: Vec<VteEventDto> = std::iter::once(marker)
+                .chain(dtos.iter().cloned())
+                .collect();
+            Html(static_view::render(&events))
+        }
+        None => Html(static_view::render(&dtos)),
+    }
+}
+
+/// A single HTML file with the session's events and a small viewer built in, so it can be opened later
+/// (or attached to a bug report) without escape-artist running
+#[axum::debug_handler]
+async fn export_html(State(state): State<AppState>) -> impl IntoResponse {
+    let dtos = state.all_dtos.lock().await;
+    let annotations = state.annotations.lock().await;
+    let notes = annotations
+        .iter()
+        .map(|(id, note)| VteEventDto::Annotation {
+            target_id: *id,
+            note: note.clone(),
+        });
+    let events: Vec<VteEventDto> = state
+        .dropped_marker()
+        .into_iter()
+        .chain(dtos.iter().cloned())
+        .chain(notes)
+        .collect();
+    // the events go inside a <script> tag, so the child's output mustn't be able to close it. `<` only
+    // shows up inside JSON strings, where it can be escaped
+    let json = serde_json::to_string(&events)
+        .unwrap()
+        .replace('<', "\\u003c");
+    let page = include_str!("../embed/export.html").replace("{{EVENTS}}", &json);
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"escape-artist-session.html\"",
+            ),
+        ],
+        page,
+    )
+}
+
+/// The session's output exactly as the child wrote it, which can be fed back into `escape-artist replay`
+#[axum::debug_handler]
+async fn raw_reconstructed(State(state): State<AppState>) -> impl IntoResponse {
+    let _dtos = state.all_dtos.lock().await;
+    let bytes = state.raw_bytes.lock().await.concat();
+    (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"escape-artist-session.bin\"",
+            ),
+        ],
+        bytes,
+    )
+}
+
+/// Forget everything decoded so far and have every client do the same, for a clean slate between
+/// experiments. The command keeps running
+#[axum::debug_handler]
+async fn clear(State(state): State<AppState>) -> impl IntoResponse {
+    state.clear().await;
+    StatusCode::NO_CONTENT
+}
+
+/// Commands that clients can send over the /events websocket
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum ClientCommand {
+    /// Show the events up to a point in time, or up to and including the event at `index`, and stop
+    /// sending live events. Seeking past the end of a replay plays it up to `index` and pauses there
+    Seek {
+        ts_ms: Option<u64>,
+        index: Option<usize>,
+    },
+    /// Go back to showing everything, including live events, and carry on with a paused replay
+    Resume,
+    /// Stop feeding a replay's sequences through
+    Pause,
+    /// Show the next event after a seek, or let one more of a paused replay's sequences through
+    Step,
+    /// Attach a note to the event at index `id`, replacing any note it already has
+    Annotate { id: usize, note: String },
+}
+
+/// A chunk of output exactly as it was read, for seeing read boundaries and timing
+#[derive(Serialize, Clone)]
+struct RawChunk {
+    /// Where the chunk starts in the output as a whole
+    offset: usize,
+    #[serde(rename = "hex", serialize_with = "serialize_hex")]
+    bytes: Vec<u8>,
+    /// ms since escape-artist started, like `AppState::timestamps_ms`
+    timestamp_ms: u64,
+}
+
+fn serialize_hex<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    serializer.serialize_str(&hex.join(" "))
+}
+
+#[axum::debug_handler]
+async fn bytes_websocket(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
+    ws.on_upgrade(move |ws: WebSocket| async move { stream_chunks(state, ws).await })
+}
+
+/// Send every chunk read so far, then new ones in batches. Separate from `/events` because it's much
+/// noisier and only useful when the parsed view itself is in doubt
+async fn stream_chunks(app_state: AppState, mut ws: WebSocket) {
+    info!("raw bytes client connected");
+    let mut rx = app_state.chunk_tx.subscribe();
+    let chunks = app_state.raw_chunks.lock().await.clone();
+    for batch in chunks.chunks(100) {
+        let json = serde_json::to_string(batch).unwrap();
+        if ws.send(Message::Text(json)).await.is_err() {
+            info!("raw bytes client disconnected");
+            return;
+        }
+    }
+
+    let mut throttle = AdaptiveThrottle::new();
+    loop {
+        let mut batch = match rx.recv().await {
+            Ok(chunk) => vec![chunk],
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("raw bytes client fell behind, {skipped} chunks were dropped");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let wait = throttle.record_event();
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+        while let Ok(chunk) = rx.try_recv() {
+            throttle.record_event();
+            batch.push(chunk);
+        }
+
+        let json = serde_json::to_string(&batch).unwrap();
+        if ws.send(Message::Text(json)).await.is_err() {
+            info!("raw bytes client disconnected");
+            return;
+        }
+    }
+}
+
 #[axum::debug_handler]
 async fn events_websocket(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|ws: WebSocket| async { stream_events(state, ws).await })
+    let filter = EventFilter::new(query.only.as_deref(), query.exclude.as_deref());
+    ws.on_upgrade(move |ws: WebSocket| async move {
+        stream_events(state, ws, query.minimal, filter, query.since).await
+    })
 }
 
-fn hex_color(color: &ColorSpec) -> Option<String> {
-    match color {
-        ColorSpec::Default => None,
-        ColorSpec::PaletteIndex(i) => {
-            let (r, g, b) = rgb_from_ansi256(*i);
-            Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+/// The next live event for a websocket client. If the client fell so far behind that the oldest
+/// events were dropped, that's a `Skipped` event saying how many, so the gap shows rather than output
+/// quietly having a chunk missing. Only fails once the channel is closed
+async fn recv_live(rx: &mut broadcast::Receiver<TimedDto>) -> Result<TimedDto, RecvError> {
+    match rx.recv().await {
+        Err(RecvError::Lagged(skipped)) => {
+            warn!("websocket client fell behind, {skipped} events were dropped");
+            Ok(VteEventDto::Skipped { count: skipped }.into())
         }
-        ColorSpec::TrueColor(srgba) => Some(srgba.to_rgb_string()),
+        received => received,
     }
 }
 
 // send all the already-logged events over the socket right away, then stream them as they occur
-async fn stream_events(app_state: AppState, mut ws: WebSocket) {
+async fn stream_events(
+    app_state: AppState,
+    mut ws: WebSocket,
+    minimal: bool,
+    filter: EventFilter,
+    since: Option<u64>,
+) {
+    info!("websocket client connected (minimal: {minimal})");
+    if minimal {
+        // minimal clients get the icons once up front, then only see icon keys
+        let icons: serde_json::Map<String, serde_json::Value> = icons::all()
+            .into_iter()
+            .map(|(key, svg)| (key.to_string(), svg.into()))
+            .collect();
+        let icon_map = serde_json::json!([{ "type": "IconMap", "icons": icons }]);
+        if ws.send(Message::Text(icon_map.to_string())).await.is_err() {
+            info!("websocket client disconnected");
+            return;
+        }
+    }
+
     let dtos = app_state.all_dtos.lock().await;
-    for chunk in dtos.chunks(100) {
-        ws.send(Message::Text(serde_json::to_string(&chunk).unwrap()))
-            .await
-            .unwrap();
+    // a reconnecting client only needs what came after `since`, unless that's been evicted or cleared
+    // since it was sent, in which case it starts from scratch like everyone else
+    let first_seq = app_state.first_seq.load(Ordering::Relaxed);
+    let resume_from = since
+        .and_then(|since| since.checked_sub(first_seq))
+        .map(|index| index as usize + 1)
+        .filter(|index| *index <= dtos.len());
+    debug!(
+        "sending {} existing events to new client",
+        dtos.len() - resume_from.unwrap_or(0)
+    );
+    let timestamps = app_state.timestamps_ms.lock().await.clone();
+    if send_dtos(
+        &mut ws,
+        &app_state,
+        &dtos,
+        &timestamps,
+        resume_from,
+        minimal,
+        &filter,
+    )
+    .await
+    .is_err()
+    {
+        info!("websocket client disconnected");
+        return;
     }
     drop(dtos);
 
     let mut rx = app_state.tx.subscribe();
     // throttle event sending so we can cut down on renders
-    const THROTTLE_DURATION: Duration = Duration::from_millis(100);
-    let mut batch = vec![];
-    let mut next_send = Instant::now() + THROTTLE_DURATION;
+    let mut throttle = AdaptiveThrottle::new();
+    let mut batch: Vec<TimedDto> = vec![];
+    // only set while there's a batch waiting to be sent
+    let mut next_send: Option<Instant> = None;
+    // after a seek, the client is looking at the first this many events, so live events are held back
+    let mut shown: Option<usize> = None;
+    let mut last_sent = Instant::now();
 
     loop {
-        if let Ok(Ok(e)) = timeout_at(next_send, rx.recv()).await {
-            // TODO rebuild this
-            // optimization: if this is a string and the last item in the batch is also a string, concatenate them
-            // this greatly cuts down on the number of events sent to the front-end
-            if let VteEventDto::Print { string, .. } = &e {
-                if let Some(VteEventDto::Print {
-                    string: last_string,
-                    ..
-                }) = batch.last_mut()
+        let keepalive_at = app_state
+            .keepalive
+            .map_or_else(far_future, |keepalive| last_sent + keepalive);
+        let send_at = next_send.unwrap_or_else(far_future);
+        let received = tokio::select! {
+            received = timeout_at(send_at, recv_live(&mut rx)) => received,
+            _ = sleep_until(keepalive_at) => {
+                trace!("pinging idle websocket client");
+                if ws.send(Message::Ping(vec![])).await.is_err() {
+                    info!("websocket client disconnected");
+                    return;
+                }
+                last_sent = Instant::now();
+                continue;
+            }
+            message = ws.recv() => {
+                let command = match message {
+                    Some(Ok(Message::Text(text))) => serde_json::from_str::<ClientCommand>(&text),
+                    Some(Ok(_)) => continue,
+                    None | Some(Err(_)) => {
+                        info!("websocket client disconnected");
+                        return;
+                    }
+                };
+                let sent = match command {
+                    Ok(ClientCommand::Seek { ts_ms, index }) => {
+                        batch.clear();
+                        next_send = None;
+                        let dtos = app_state.all_dtos.lock().await;
+                        let timestamps = app_state.timestamps_ms.lock().await;
+                        let count = match (index, ts_ms) {
+                            (Some(index), _) => {
+                                debug!("client seeked to event {index}");
+                                app_state.playback.send_replace(if index >= dtos.len() {
+                                    Playback::RunningTo(index)
+                                } else {
+                                    Playback::Paused
+                                });
+                                (index + 1).min(dtos.len())
+                            }
+                            (None, Some(ts_ms)) => {
+                                debug!("client seeked to {ts_ms}ms");
+                                timestamps.partition_point(|ts| *ts <= ts_ms)
+                            }
+                            (None, None) => dtos.len(),
+                        };
+                        // the replay will play up to the index, so the client may as well see it happen
+                        shown = (count < dtos.len() || index.is_none()).then_some(count);
+                        send_dtos(
+                            &mut ws,
+                            &app_state,
+                            &dtos[..count],
+                            &timestamps[..count],
+                            None,
+                            minimal,
+                            &filter,
+                        )
+                        .await
+                    }
+                    Ok(ClientCommand::Resume) => {
+                        debug!("client resumed");
+                        shown = None;
+                        app_state.playback.send_replace(Playback::Playing);
+                        batch.clear();
+                        next_send = None;
+                        let dtos = app_state.all_dtos.lock().await;
+                        let timestamps = app_state.timestamps_ms.lock().await;
+                        send_dtos(
+                            &mut ws,
+                            &app_state,
+                            &dtos,
+                            &timestamps,
+                            None,
+                            minimal,
+                            &filter,
+                        )
+                        .await
+                    }
+                    Ok(ClientCommand::Pause) => {
+                        debug!("client paused");
+                        app_state.playback.send_replace(Playback::Paused);
+                        Ok(())
+                    }
+                    Ok(ClientCommand::Step) => {
+                        let dtos = app_state.all_dtos.lock().await;
+                        match shown {
+                            // show the next event the client has been held back from
+                            Some(count) if count < dtos.len() => {
+                                // events the client filtered out don't count as a step
+                                let index = (count..dtos.len())
+                                    .find(|i| filter.allows(&dtos[*i]))
+                                    .unwrap_or(dtos.len() - 1);
+                                debug!("client stepped to event {index}");
+                                shown = (index + 1 < dtos.len()).then_some(index + 1);
+                                if filter.allows(&dtos[index]) {
+                                    let ts_ms = app_state.timestamps_ms.lock().await[index];
+                                    let seq = app_state.seq(index);
+                                    let dto = TimedDto::new(dtos[index].clone(), ts_ms, seq);
+                                    ws.send(Message::Text(serialize_dtos(&[dto], minimal))).await
+                                } else {
+                                    Ok(())
+                                }
+                            }
+                            _ => {
+                                debug!("client stepped the replay");
+                                shown = None;
+                                app_state.playback.send_modify(|playback| {
+                                    *playback = match *playback {
+                                        Playback::Stepping(steps) => Playback::Stepping(steps + 1),
+                                        _ => Playback::Stepping(1),
+                                    }
+                                });
+                                Ok(())
+                            }
+                        }
+                    }
+                    Ok(ClientCommand::Annotate { id, note }) => {
+                        debug!("client annotated event {id}");
+                        app_state.annotate(id, note).await;
+                        Ok(())
+                    }
+                    Err(e) => {
+                        warn!("ignoring unrecognized websocket command: {e}");
+                        Ok(())
+                    }
+                };
+                if sent.is_err() {
+                    info!("websocket client disconnected");
+                    return;
+                }
+                last_sent = Instant::now();
+                continue;
+            }
+        };
+
+        match received {
+            Ok(Ok(TimedDto {
+                dto: VteEventDto::Clear {},
+                ..
+            })) => {
+                // whatever the client was looking at is gone, so there's nothing to stay paused on
+                shown = None;
+                batch.clear();
+                next_send = None;
+                if send_dtos(&mut ws, &app_state, &[], &[], None, minimal, &filter)
+                    .await
+                    .is_err()
                 {
-                    last_string.push_str(string);
-                    continue;
+                    info!("websocket client disconnected");
+                    return;
                 }
+                last_sent = Instant::now();
             }
+            // a paused client gets everything resent when it resumes, so it hasn't really missed
+            // anything that was skipped either
+            Ok(Ok(_)) if shown.is_some() => {}
+            Ok(Ok(
+                e @ TimedDto {
+                    dto: VteEventDto::Skipped { .. },
+                    ..
+                },
+            )) => {
+                batch.push(e);
+                next_send.get_or_insert_with(Instant::now);
+            }
+            Ok(Ok(e)) if !filter.allows(&e.dto) => {}
+            Ok(Ok(e)) => {
+                let interval = throttle.record_event();
+                if next_send.is_none() {
+                    next_send = Some(Instant::now() + interval);
+                }
 
-            batch.push(e)
+                // TODO rebuild this
+                // optimization: if this is a string and the last item in the batch is also a string, concatenate them
+                // this greatly cuts down on the number of events sent to the front-end
+                // (the merged print keeps the first one's timestamp)
+                if !batch
+                    .last_mut()
+                    .is_some_and(|last| last.dto.merge_print(&e.dto))
+                {
+                    batch.push(e);
+                }
+            }
+            Ok(Err(_)) => {
+                debug!("event channel closed, no more events to send");
+                if !batch.is_empty() {
+                    let _ = ws
+                        .send(Message::Text(serialize_dtos(&batch, minimal)))
+                        .await;
+                }
+                return;
+            }
+            // time to send the batch
+            Err(_) => {}
         }
 
-        if Instant::now() > next_send {
+        if next_send.is_some_and(|next_send| Instant::now() >= next_send) {
             if !batch.is_empty() {
                 if ws
-                    .send(Message::Text(serde_json::to_string(&batch).unwrap()))
+                    .send(Message::Text(serialize_dtos(&batch, minimal)))
                     .await
                     .is_err()
                 {
                     // if this failed it's probably because the client disconnected
+                    info!("websocket client disconnected");
                     return;
                 }
+                trace!("sent a batch of {} events", batch.len());
                 batch.clear();
+                last_sent = Instant::now();
             }
-            next_send = Instant::now() + THROTTLE_DURATION;
+            next_send = None;
         }
     }
 }
 
-fn update_print_colors(dto: &mut VteEventDto, fg_color: ColorSpec, bg_color: ColorSpec) {
-    if let VteEventDto::Print {
-        color: dto_color,
-        bg_color: dto_bg_color,
-        ..
-    } = dto
-    {
-        *dto_color = hex_color(&fg_color);
-        *dto_bg_color = hex_color(&bg_color);
-    }
+/// Decides how long to batch up events before sending them. When events are sparse (like when someone
+/// is typing) they're sent right away, but during a flood of output we wait longer so the browser renders less often
+struct AdaptiveThrottle {
+    /// Exponentially weighted average of the time between events
+    average_gap: Duration,
+    last_event: Instant,
 }
 
-fn update_global_colors(action: &Action, fg_color: &mut ColorSpec, bg_color: &mut ColorSpec) {
-    if let Action::CSI(CSI::Sgr(sgr)) = action {
-        match sgr {
-            Sgr::Foreground(color) => {
-                *fg_color = *color;
-            }
-            Sgr::Background(color) => {
-                *bg_color = *color;
-            }
-            Sgr::Reset => {
-                *fg_color = ColorSpec::Default;
-                *bg_color = ColorSpec::Default;
-            }
-            _ => {}
+impl AdaptiveThrottle {
+    /// Below this rate (events/sec), events are sent immediately
+    const SPARSE_RATE: f64 = 20.0;
+    const MAX_INTERVAL: Duration = Duration::from_millis(250);
+
+    fn new() -> Self {
+        Self {
+            average_gap: Duration::from_secs(1),
+            last_event: Instant::now(),
         }
     }
-}
 
-struct CleanUp;
+    /// Record that an event arrived and get how long to wait before sending it
+    fn record_event(&mut self) -> Duration {
+        let now = Instant::now();
+        let gap = now - self.last_event;
+        self.last_event = now;
+        self.average_gap = self.average_gap.mul_f64(0.8) + gap.mul_f64(0.2);
 
-impl Drop for CleanUp {
-    fn drop(&mut self) {
-        terminal::disable_raw_mode().expect("Could not disable raw mode");
-        execute!(stdout(), cursor::SetCursorStyle::DefaultUserShape).unwrap();
+        let rate = 1.0 / self.average_gap.as_secs_f64().max(f64::EPSILON);
+        if rate < Self::SPARSE_RATE {
+            Duration::ZERO
+        } else {
+            // 100 events/sec waits 10ms, 1000 events/sec waits 100ms
+            Duration::from_secs_f64(rate / 10_000.0).min(Self::MAX_INTERVAL)
+        }
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
-#[serde(tag = "type")] // give each JSON record a "type" field indicating the enum type, easier to consume from JS
-enum VteEventDto {
-    Print {
-        string: String,
-        color: Option<String>,
-        bg_color: Option<String>,
-    },
-    GenericEscape {
-        title: Option<String>,
-        icon_svg: Option<String>,
-        tooltip: Option<String>,
-        raw_bytes: String,
-    },
-    ColorEscape {
-        title: Option<String>,
-        icon_svg: Option<String>,
-        tooltip: Option<String>,
-        color: String,
-        raw_bytes: String,
-    },
-    InvisibleLineBreak {},
-    LineBreak {
-        title: String,
-    },
+/// A deadline that will never be hit in practice
+fn far_future() -> Instant {
+    Instant::now() + Duration::from_secs(60 * 60 * 24)
 }
 
-impl From<&(Action, Vec<u8>)> for VteEventDto {
-    fn from(value: &(Action, Vec<u8>)) -> Self {
-        let (action, raw_bytes) = value;
-        match action {
-            Action::Print(c) => VteEventDto::Print {
-                string: c.to_string(),
-                color: None,
-                bg_color: None,
-            },
-            Action::PrintString(s) => VteEventDto::Print {
-                string: s.clone(),
-                color: None,
-                bg_color: None,
-            },
-            Action::Control(ctrl) => ctrl_to_dto(ctrl),
-            Action::DeviceControl(dcm) => VteEventDto::GenericEscape {
-                title: Some("DCM".into()),
-                icon_svg: None,
-                tooltip: Some(format!("{dcm:?}")),
-                raw_bytes: sanitize_raw_bytes(raw_bytes),
-            },
-            Action::OperatingSystemCommand(osc) => osc_to_dto(osc, raw_bytes),
-            Action::CSI(csi) => csi_to_dto(csi, sanitize_raw_bytes(raw_bytes)),
-            Action::Esc(e) => esc_to_dto(e, raw_bytes),
-            Action::Sixel(_) => VteEventDto::GenericEscape {
-                title: Some("Sixel".into()),
-                icon_svg: Some(iconify::svg!("mdi:image").into()),
-                tooltip: Some("Sixel image".into()),
-                raw_bytes: sanitize_raw_bytes(raw_bytes),
-            },
-            Action::XtGetTcap(x) => VteEventDto::GenericEscape {
-                title: Some("XTGETTCAP".into()),
-                icon_svg: None,
-                tooltip: Some(format!("Get termcap, terminfo for: {}", x.join(", "))),
-                raw_bytes: sanitize_raw_bytes(raw_bytes),
-            },
-            Action::KittyImage(_) => VteEventDto::GenericEscape {
-                title: Some("Kitty".into()),
-                icon_svg: Some(iconify::svg!("mdi:image").into()),
-                tooltip: Some("Kitty image".into()),
-                raw_bytes: sanitize_raw_bytes(raw_bytes),
-            },
-        }
+/// Send events from the start of `all_dtos`, which the caller has locked, with their annotations. Unless
+/// the client already has the events before `resume_from`, it's told to clear what it has first
+async fn send_dtos(
+    ws: &mut WebSocket,
+    app_state: &AppState,
+    dtos: &[VteEventDto],
+    timestamps: &[u64],
+    resume_from: Option<usize>,
+    minimal: bool,
+    filter: &EventFilter,
+) -> Result<(), axum::Error> {
+    if resume_from.is_none() {
+        let leading: Vec<VteEventDto> = std::iter::once(VteEventDto::Clear {})
+            .chain(app_state.dropped_marker())
+            .filter(|dto| filter.allows(dto))
+            .collect();
+        ws.send(Message::Text(serialize_dtos(&leading, minimal)))
+            .await?;
     }
+    let start = resume_from.unwrap_or(0);
+    let timed: Vec<TimedDto> = dtos
+        .iter()
+        .zip(timestamps)
+        .enumerate()
+        .skip(start)
+        .filter(|(_, (dto, _))| filter.allows(dto))
+        .map(|(index, (dto, ts_ms))| TimedDto::new(dto.clone(), *ts_ms, app_state.seq(index)))
+        .collect();
+    for chunk in timed.chunks(100) {
+        ws.send(Message::Text(serialize_dtos(chunk, minimal)))
+            .await?;
+    }
+    let annotations: Vec<_> = app_state
+        .annotations
+        .lock()
+        .await
+        .range(start..dtos.len())
+        .map(|(id, note)| VteEventDto::Annotation {
+            target_id: *id,
+            note: note.clone(),
+        })
+        .filter(|dto| filter.allows(dto))
+        .collect();
+    if !annotations.is_empty() {
+        ws.send(Message::Text(serialize_dtos(&annotations, minimal)))
+            .await?;
+    }
+    Ok(())
 }
 
-fn osc_to_dto(osc: &OperatingSystemCommand, raw_bytes: &[u8]) -> VteEventDto {
-    let raw_bytes_str = sanitize_raw_bytes(raw_bytes);
-    match osc {
-        OperatingSystemCommand::SetHyperlink(link) => match link {
-            Some(link) => VteEventDto::GenericEscape {
-                title: None,
-                icon_svg: Some(iconify::svg!("mdi:link").into()),
-                tooltip: Some(format!("Set hyperlink: {link}")),
-                raw_bytes: raw_bytes_str,
-            },
-            None => VteEventDto::GenericEscape {
-                title: None,
-                icon_svg: Some(iconify::svg!("mdi:link-off").into()),
-                tooltip: Some("Clear hyperlink".into()),
-                raw_bytes: raw_bytes_str,
-            },
-        },
-        _ => VteEventDto::GenericEscape {
-            title: Some("OSC".into()),
-            icon_svg: None,
-            tooltip: Some(format!("{osc:?}")),
-            raw_bytes: sanitize_raw_bytes(raw_bytes),
-        },
-    }
-}
-
-fn esc_to_dto(esc: &Esc, raw_bytes: &[u8]) -> VteEventDto {
-    let raw_bytes_str = sanitize_raw_bytes(raw_bytes);
-    match esc {
-        Esc::Unspecified { .. } => VteEventDto::GenericEscape {
-            title: None,
-            icon_svg: Some(iconify::svg!("mdi:question-mark-box").into()),
-            tooltip: Some("Unspecified escape sequence".into()),
-            raw_bytes: raw_bytes_str,
-        },
-        Esc::Code(code) => match code {
-            EscCode::StringTerminator => VteEventDto::GenericEscape {
-                title: Some("\\".into()),
-                icon_svg: None,
-                tooltip: Some("ST / String Terminator".into()),
-                raw_bytes: raw_bytes_str,
-            },
-            EscCode::DecSaveCursorPosition => VteEventDto::GenericEscape {
-                title: None,
-                icon_svg: Some(iconify::svg!("mdi:content-save").into()),
-                tooltip: Some("Save cursor position".into()),
-                raw_bytes: raw_bytes_str,
-            },
-            EscCode::DecRestoreCursorPosition => VteEventDto::GenericEscape {
-                title: None,
-                icon_svg: Some(iconify::svg!("mdi:file-restore").into()),
-                tooltip: Some("Restore cursor position".into()),
-                raw_bytes: raw_bytes_str,
-            },
-            EscCode::AsciiCharacterSetG0 | EscCode::AsciiCharacterSetG1 => {
-                VteEventDto::GenericEscape {
-                    title: None,
-                    icon_svg: Some(iconify::svg!("mdi:alphabetical-variant").into()),
-                    tooltip: Some(format!("{code:?}")),
-                    raw_bytes: raw_bytes_str,
-                }
-            }
-            _ => VteEventDto::GenericEscape {
-                title: Some("ESC".into()),
-                icon_svg: None,
-                tooltip: Some(format!("{code:?}")),
-                raw_bytes: raw_bytes_str,
-            },
-        },
-    }
-}
-
-fn ctrl_to_dto(ctrl: &ControlCode) -> VteEventDto {
-    let as_byte = *ctrl as u8;
-    let raw_bytes = format!("{:#02x}", as_byte);
-
-    match ctrl {
-        ControlCode::Bell => VteEventDto::GenericEscape {
-            title: None,
-            icon_svg: Some(iconify::svg!("mdi:bell").into()),
-            tooltip: Some("Bell".into()),
-            raw_bytes,
-        },
-        ControlCode::Backspace => VteEventDto::GenericEscape {
-            title: None,
-            icon_svg: Some(iconify::svg!("mdi:backspace").into()),
-            tooltip: Some("Backspace".into()),
-            raw_bytes,
-        },
-        ControlCode::HorizontalTab => VteEventDto::GenericEscape {
-            title: None,
-            icon_svg: Some(iconify::svg!("mdi:keyboard-tab").into()),
-            tooltip: Some("Tab".into()),
-            raw_bytes,
-        },
-        ControlCode::LineFeed => VteEventDto::LineBreak { title: "LF".into() },
-        ControlCode::CarriageReturn => VteEventDto::LineBreak { title: "CR".into() },
-        _ => VteEventDto::GenericEscape {
-            title: Some(format!("{ctrl:?}")),
-            icon_svg: None,
-            tooltip: None,
-            raw_bytes,
-        },
-    }
-}
-
-fn csi_to_dto(csi: &CSI, raw_bytes: String) -> VteEventDto {
-    let (title, tooltip, icon_svg) = match csi {
-        CSI::Sgr(sgr) => match sgr {
-            Sgr::Reset => (
-                None,
-                Some("SGR (Select Graphic Rendition) Reset (reset all styles)".into()),
-                Some(iconify::svg!("carbon:reset").into()),
-            ),
-            Sgr::Foreground(color) => {
-                return VteEventDto::ColorEscape {
-                    title: Some("FG".into()),
-                    icon_svg: None,
-                    tooltip: Some(format!("Set foreground color to: {color:?}")),
-                    color: hex_color(color).unwrap_or("black".into()),
-                    raw_bytes,
-                }
-            }
-            Sgr::Background(color) => {
-                return VteEventDto::ColorEscape {
-                    title: Some("BG".into()),
-                    icon_svg: None,
-                    tooltip: Some(format!("Set background color to: {color:?}")),
-                    color: hex_color(color).unwrap_or("black".into()),
-                    raw_bytes,
-                }
-            }
-            _ => (Some("SGR".into()), Some(format!("Set {sgr:?}")), None),
-        },
-        CSI::Cursor(cursor) => (
-            None,
-            Some(format!("Update cursor: {cursor:?}")),
-            Some(iconify::svg!("ph:cursor-text-fill").into()),
-        ),
-        CSI::Edit(edit) => match edit {
-            Edit::EraseInLine(erase) => (
-                None,
-                Some(match erase {
-                    EraseInLine::EraseToEndOfLine => "Erase to end of line".into(),
-                    EraseInLine::EraseToStartOfLine => "Erase to start of line".into(),
-                    EraseInLine::EraseLine => "Erase line".into(),
-                }),
-                Some(iconify::svg!("mdi:eraser").into()),
-            ),
-            Edit::EraseInDisplay(erase) => (
-                None,
-                Some(match erase {
-                    EraseInDisplay::EraseToEndOfDisplay => "Erase to end of display".into(),
-                    EraseInDisplay::EraseToStartOfDisplay => "Erase to start of display".into(),
-                    EraseInDisplay::EraseDisplay => "Erase display".into(),
-                    EraseInDisplay::EraseScrollback => "Erase scrollback".into(),
-                }),
-                Some(iconify::svg!("mdi:eraser").into()),
-            ),
-            _ => (Some("Edit".into()), Some(format!("{edit:?}")), None),
-        },
-        // CSI::Edit(_) => todo!(),
-        // CSI::Mode(_) => todo!(),
-        // CSI::Device(_) => todo!(),
-        // CSI::Mouse(_) => todo!(),
-        // CSI::Window(_) => todo!(),
-        // CSI::Keyboard(_) => todo!(),
-        // CSI::SelectCharacterPath(_, _) => todo!(),
-        // CSI::Unspecified(_) => todo!(),
-        _ => (Some("CSI".into()), Some(format!("{csi:?}")), None),
-    };
-
-    VteEventDto::GenericEscape {
-        title,
-        tooltip,
-        icon_svg,
-        raw_bytes,
-    }
+struct CleanUp {
+    /// Whether we put the terminal in raw mode and need to take it back out
+    raw_mode: bool,
 }
 
-/// Convert escape code bytes into a user-facing string,
-/// replacing control codes with their \0x hex representations
-fn sanitize_raw_bytes(raw_bytes: &[u8]) -> String {
-    let ret = String::from_utf8_lossy(raw_bytes);
-    // TODO: there's gotta be a better way to do this than a line for every interesting control char
-    ret.replace("", r"\x1b")
+impl Drop for CleanUp {
+    fn drop(&mut self) {
+        // best effort; these fail when there's no terminal, e.g. when explain's output is piped somewhere
+        if self.raw_mode {
+            let _ = terminal::disable_raw_mode();
+        }
+        let _ = execute!(stdout(), cursor::SetCursorStyle::DefaultUserShape);
+    }
 }
 
 pub struct StaticFile<T>(pub T);
@@ -809,3 +2652,78 @@ fn after_help() -> StyledStr {
     "It's written+maintained by Reilly Wood, and the latest version can be found at https://github.com/rgwood/escape-artist/")
     .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn clear_blanks_the_screen_at_its_current_size() {
+        let state = AppState::new(None, 100);
+        {
+            let mut screen = state.screen.lock().await;
+            screen.resize(40, 10);
+            for action in Parser::new().parse_as_vec(b"hello") {
+                screen.apply(&action);
+            }
+        }
+        state.clear().await;
+        let snapshot = state.screen.lock().await.snapshot();
+        assert_eq!((snapshot.cols, snapshot.rows), (40, 10));
+        assert_eq!((snapshot.cursor_row, snapshot.cursor_col), (0, 0));
+        assert!(state.screen.lock().await.text().trim().is_empty());
+    }
+
+    #[tokio::test]
+    async fn lagging_behind_is_a_skipped_event() {
+        let (tx, mut rx) = broadcast::channel::<TimedDto>(2);
+        for _ in 0..5 {
+            tx.send(VteEventDto::Clear {}.into()).unwrap();
+        }
+        let received = recv_live(&mut rx).await.unwrap();
+        assert!(matches!(received.dto, VteEventDto::Skipped { count: 3 }));
+        // then carries on with the events that are still there
+        assert!(matches!(
+            recv_live(&mut rx).await.unwrap().dto,
+            VteEventDto::Clear {}
+        ));
+    }
+
+    #[tokio::test]
+    async fn activity_copes_with_timestamps_out_of_order() {
+        let state = AppState::new(None, 100);
+        *state.timestamps_ms.lock().await = vec![50, 10];
+        *state.categories.lock().await = vec!["Print", "CSI"];
+        let response = activity(State(state), Query(ActivityQuery { buckets: Some(5) }))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn timeline_copes_with_timestamps_out_of_order() {
+        let state = AppState::new(None, 100);
+        *state.timestamps_ms.lock().await = vec![50, 10];
+        let response = timeline(State(state)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn check_judges_dcs_strings_whole() {
+        let dir = std::env::temp_dir();
+        let decrqss = dir.join(format!("escape-artist-decrqss-{}", std::process::id()));
+        std::fs::write(&decrqss, b"\x1bP$q q\x1b\\").unwrap();
+        let unknown = dir.join(format!("escape-artist-unknown-dcs-{}", std::process::id()));
+        std::fs::write(&unknown, b"\x1bPzzz\x1b\\").unwrap();
+
+        let explained = explain_file(decrqss.to_str().unwrap()).unwrap();
+        assert_eq!(explained.len(), 1);
+        assert_eq!((explained[0].offset, explained[0].category), (0, "DCS"));
+        assert!(!explained[0].undecoded);
+        assert!(check(decrqss.to_str().unwrap()).is_ok());
+        assert!(check(unknown.to_str().unwrap()).is_err());
+
+        std::fs::remove_file(decrqss).unwrap();
+        std::fs::remove_file(unknown).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+//! A virtual screen that sequences are played onto, so the final rendered output can be shown next to the
+//! sequences that produced it. termwiz's `Surface` does the grid, wrapping and scrolling; this module
+//! translates parsed `Action`s into the `Change`s it understands
+
+use serde::Serialize;
+use termwiz::{
+    cell::{AttributeChange, CellAttributes, Intensity, Underline},
+    color::{ColorAttribute, ColorSpec},
+    escape::{
+        csi::{Cursor, Edit, EraseInDisplay, EraseInLine, Sgr, Window},
+        Action, ControlCode, Esc, EscCode, CSI,
+    },
+    surface::{Change, Position, Surface},
+};
+
+use crate::{hex_color, TextStyle};
+
+/// The size the screen starts at when nothing says otherwise
+pub const DEFAULT_COLS: usize = 80;
+pub const DEFAULT_ROWS: usize = 24;
+
+/// The most columns or rows the screen will grow to. Sizes can come from the output being inspected
+/// (XTWINOPS), so they can't be trusted to be reasonable
+pub const MAX_SIZE: usize = 1000;
+
+/// Tab stops every 8 columns, like a freshly reset terminal
+const TAB_WIDTH: usize = 8;
+
+pub struct VirtualScreen {
+    surface: Surface,
+    /// Where DECSC or `CSI s` saved the cursor, as (column, row)
+    saved_cursor: Option<(usize, usize)>,
+    /// The background set by the last SGR, which `Surface` doesn't expose
+    background: ColorAttribute,
+}
+
+impl Default for VirtualScreen {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLS, DEFAULT_ROWS)
+    }
+}
+
+impl VirtualScreen {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            surface: Surface::new(cols.clamp(1, MAX_SIZE), rows.clamp(1, MAX_SIZE)),
+            saved_cursor: None,
+            background: ColorAttribute::Default,
+        }
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.surface
+            .resize(cols.clamp(1, MAX_SIZE), rows.clamp(1, MAX_SIZE));
+    }
+
+    /// (columns, rows)
+    pub fn size(&self) -> (usize, usize) {
+        self.surface.dimensions()
+    }
+
+    /// Play one action onto the screen. Anything that doesn't change what's displayed, or that we don't
+    /// emulate (scroll regions, the alternate screen, insert mode and so on), is ignored
+    pub fn apply(&mut self, action: &Action) {
+        match action {
+            Action::Print(c) => self.add(Change::Text(c.to_string())),
+            Action::PrintString(s) => self.add(Change::Text(s.clone())),
+            Action::Control(control) => self.apply_control(control),
+            Action::CSI(CSI::Cursor(cursor)) => self.apply_cursor(cursor),
+            Action::CSI(CSI::Edit(edit)) => self.apply_edit(edit),
+            Action::CSI(CSI::Sgr(sgr)) => self.apply_sgr(sgr),
+            Action::CSI(CSI::Window(window)) => {
+                if let Window::ResizeWindowCells {
+                    width: Some(cols),
+                    height: Some(rows),
+                } = &**window
+                {
+                    if *cols > 0 && *rows > 0 {
+                        self.resize(*cols as usize, *rows as usize);
+                    }
+                }
+            }
+            Action::Esc(Esc::Code(EscCode::DecSaveCursorPosition)) => self.save_cursor(),
+            Action::Esc(Esc::Code(EscCode::DecRestoreCursorPosition)) => self.restore_cursor(),
+            Action::Esc(Esc::Code(EscCode::FullReset)) => {
+                self.saved_cursor = None;
+                self.background = ColorAttribute::Default;
+                self.add(Change::AllAttributes(CellAttributes::default()));
+                self.add(Change::ClearScreen(ColorAttribute::Default));
+            }
+            _ => {}
+        }
+    }
+
+    fn add(&mut self, change: Change) {
+        self.surface.add_change(change);
+    }
+
+    fn move_to(&mut self, x: Position, y: Position) {
+        self.add(Change::CursorPosition { x, y });
+    }
+
+    fn apply_control(&mut self, control: &ControlCode) {
+        match control {
+            ControlCode::CarriageReturn => self.add(Change::Text("\r".to_string())),
+            // assume the pty translates LF to CRLF, like it does by default
+            ControlCode::LineFeed => self.add(Change::Text("\r\n".to_string())),
+            ControlCode::VerticalTab | ControlCode::FormFeed => {
+                self.add(Change::Text("\n".to_string()))
+            }
+            ControlCode::Backspace => self.move_to(Position::Relative(-1), Position::Relative(0)),
+            ControlCode::HorizontalTab => {
+                let (col, _) = self.surface.cursor_position();
+                let (cols, _) = self.surface.dimensions();
+                let stop = (col + TAB_WIDTH - col % TAB_WIDTH).min(cols - 1);
+                self.move_to(Position::Absolute(stop), Position::Relative(0));
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_cursor(&mut self, cursor: &Cursor) {
+        let n = |n: &u32| *n as isize;
+        match cursor {
+            Cursor::Position { line, col } | Cursor::CharacterAndLinePosition { line, col } => self
+                .move_to(
+                    Position::Absolute(col.as_zero_based() as usize),
+                    Position::Absolute(line.as_zero_based() as usize),
+                ),
+            Cursor::CharacterAbsolute(col) | Cursor::CharacterPositionAbsolute(col) => self
+                .move_to(
+                    Position::Absolute(col.as_zero_based() as usize),
+                    Position::Relative(0),
+                ),
+            Cursor::LinePositionAbsolute(line) => self.move_to(
+                Position::Relative(0),
+                Position::Absolute((*line as usize).saturating_sub(1)),
+            ),
+            Cursor::Up(rows) | Cursor::LinePositionBackward(rows) => {
+                self.move_to(Position::Relative(0), Position::Relative(-n(rows)))
+            }
+            Cursor::Down(rows) | Cursor::LinePositionForward(rows) => {
+                self.move_to(Position::Relative(0), Position::Relative(n(rows)))
+            }
+            Cursor::PrecedingLine(rows) => {
+                self.move_to(Position::Absolute(0), Position::Relative(-n(rows)))
+            }
+            Cursor::NextLine(rows) => {
+                self.move_to(Position::Absolute(0), Position::Relative(n(rows)))
+            }
+            Cursor::Left(cols) | Cursor::CharacterPositionBackward(cols) => {
+                self.move_to(Position::Relative(-n(cols)), Position::Relative(0))
+            }
+            Cursor::Right(cols) | Cursor::CharacterPositionForward(cols) => {
+                self.move_to(Position::Relative(n(cols)), Position::Relative(0))
+            }
+            Cursor::SaveCursor => self.save_cursor(),
+            Cursor::RestoreCursor => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.surface.cursor_position());
+    }
+
+    fn restore_cursor(&mut self) {
+        let (x, y) = self.saved_cursor.unwrap_or((0, 0));
+        self.move_to(Position::Absolute(x), Position::Absolute(y));
+    }
+
+    /// `Surface` can only clear from the cursor onwards, so erasing before the cursor is done by clearing
+    /// from the start of each line and then putting the cursor back
+    fn apply_edit(&mut self, edit: &Edit) {
+        let (x, y) = self.surface.cursor_position();
+        let (cols, _) = self.surface.dimensions();
+        // erased cells take the current background color, like they do in xterm
+        let bg = self.background;
+        match edit {
+            Edit::EraseInLine(EraseInLine::EraseToEndOfLine) => {
+                self.add(Change::ClearToEndOfLine(bg))
+            }
+            Edit::EraseInLine(EraseInLine::EraseToStartOfLine) => {
+                self.move_to(Position::Absolute(0), Position::Relative(0));
+                self.add(Change::Text(" ".repeat((x + 1).min(cols))));
+                self.move_to(Position::Absolute(x), Position::Absolute(y));
+            }
+            Edit::EraseInLine(EraseInLine::EraseLine) => {
+                self.move_to(Position::Absolute(0), Position::Relative(0));
+                self.add(Change::ClearToEndOfLine(bg));
+                self.move_to(Position::Absolute(x), Position::Absolute(y));
+            }
+            Edit::EraseInDisplay(EraseInDisplay::EraseToEndOfDisplay) => {
+                self.add(Change::ClearToEndOfScreen(bg))
+            }
+            Edit::EraseInDisplay(EraseInDisplay::EraseToStartOfDisplay) => {
+                for row in 0..y {
+                    self.move_to(Position::Absolute(0), Position::Absolute(row));
+                    self.add(Change::ClearToEndOfLine(bg));
+                }
+                self.move_to(Position::Absolute(0), Position::Absolute(y));
+                self.add(Change::Text(" ".repeat((x + 1).min(cols))));
+                self.move_to(Position::Absolute(x), Position::Absolute(y));
+            }
+            Edit::EraseInDisplay(EraseInDisplay::EraseDisplay) => {
+                // clearing the screen also homes the cursor in `Surface`, but not in a real terminal
+                self.add(Change::ClearScreen(bg));
+                self.move_to(Position::Absolute(x), Position::Absolute(y));
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_sgr(&mut self, sgr: &Sgr) {
+        let change = match sgr {
+            Sgr::Reset => {
+                self.background = ColorAttribute::Default;
+                Change::AllAttributes(CellAttributes::default())
+            }
+            Sgr::Foreground(color) => AttributeChange::Foreground((*color).into()).into(),
+            Sgr::Background(color) => {
+                self.background = (*color).into();
+                AttributeChange::Background(self.background).into()
+            }
+            Sgr::Intensity(intensity) => AttributeChange::Intensity(*intensity).into(),
+            Sgr::Italic(on) => AttributeChange::Italic(*on).into(),
+            Sgr::Underline(underline) => AttributeChange::Underline(*underline).into(),
+            Sgr::StrikeThrough(on) => AttributeChange::StrikeThrough(*on).into(),
+            Sgr::Inverse(on) => AttributeChange::Reverse(*on).into(),
+            _ => return,
+        };
+        self.add(change);
+    }
+
+    /// The screen's contents, with each row split into runs of identically styled text
+    pub fn snapshot(&self) -> ScreenSnapshot {
+        let (cols, rows) = self.surface.dimensions();
+        let (cursor_col, cursor_row) = self.surface.cursor_position();
+        let lines = self
+            .surface
+            .screen_lines()
+            .iter()
+            .map(|line| {
+                let mut spans: Vec<ScreenSpan> = vec![];
+                for cell in line.visible_cells() {
+                    let span = ScreenSpan::new(cell.str(), cell.attrs());
+                    match spans.last_mut() {
+                        Some(last) if last.same_style(&span) => last.text.push_str(&span.text),
+                        _ => spans.push(span),
+                    }
+                }
+                spans
+            })
+            .collect();
+        ScreenSnapshot {
+            cols,
+            rows,
+            cursor_row,
+            cursor_col,
+            lines,
+        }
+    }
+
+    /// The screen's contents as plain text, one line per row with trailing spaces trimmed
+    pub fn text(&self) -> String {
+        self.surface
+            .screen_chars_to_string()
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// What the virtual screen looks like right now. Rows and columns are 0-based
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ScreenSnapshot {
+    pub cols: usize,
+    pub rows: usize,
+    pub cursor_row: usize,
+    pub cursor_col: usize,
+    pub lines: Vec<Vec<ScreenSpan>>,
+}
+
+/// A run of cells on one row that share colors and styles
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ScreenSpan {
+    pub text: String,
+    pub color: Option<String>,
+    pub bg_color: Option<String>,
+    pub underline: bool,
+    #[serde(flatten)]
+    pub style: TextStyle,
+}
+
+impl ScreenSpan {
+    fn new(text: &str, attrs: &CellAttributes) -> Self {
+        let mut color = hex_color(&color_spec(attrs.foreground()));
+        let mut bg_color = hex_color(&color_spec(attrs.background()));
+        let reverse = attrs.reverse();
+        if reverse {
+            std::mem::swap(&mut color, &mut bg_color);
+        }
+        Self {
+            text: text.to_string(),
+            color,
+            bg_color,
+            underline: attrs.underline() != Underline::None,
+            style: TextStyle {
+                bold: attrs.intensity() == Intensity::Bold,
+                dim: attrs.intensity() == Intensity::Half,
+                italic: attrs.italic(),
+                strikethrough: attrs.strikethrough(),
+                inverse: reverse,
+            },
+        }
+    }
+
+    fn same_style(&self, other: &ScreenSpan) -> bool {
+        self.color == other.color
+            && self.bg_color == other.bg_color
+            && self.underline == other.underline
+            && self.style == other.style
+    }
+}
+
+fn color_spec(color: ColorAttribute) -> ColorSpec {
+    match color {
+        ColorAttribute::Default => ColorSpec::Default,
+        ColorAttribute::PaletteIndex(i) => ColorSpec::PaletteIndex(i),
+        ColorAttribute::TrueColorWithPaletteFallback(srgba, _)
+        | ColorAttribute::TrueColorWithDefaultFallback(srgba) => ColorSpec::TrueColor(srgba),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termwiz::escape::parser::Parser;
+
+    fn render(bytes: &[u8]) -> VirtualScreen {
+        let mut screen = VirtualScreen::new(20, 5);
+        for action in Parser::new().parse_as_vec(bytes) {
+            screen.apply(&action);
+        }
+        screen
+    }
+
+    #[test]
+    fn prints_and_moves_the_cursor() {
+        let screen = render(b"hello\r\nworld\x1b[1;3HX");
+        assert_eq!(screen.text(), "heXlo\nworld\n\n\n");
+        let snapshot = screen.snapshot();
+        assert_eq!((snapshot.cursor_row, snapshot.cursor_col), (0, 3));
+    }
+
+    #[test]
+    fn erases_lines() {
+        let screen = render(b"abcdef\x1b[3D\x1b[K\r\nghijkl\x1b[1K");
+        assert_eq!(screen.text(), "abc\n\n\n\n");
+        assert_eq!(screen.snapshot().cursor_col, 6);
+    }
+
+    #[test]
+    fn clearing_the_screen_keeps_the_cursor() {
+        let screen = render(b"abc\r\ndef\x1b[2J");
+        assert_eq!(screen.text(), "\n\n\n\n");
+        let snapshot = screen.snapshot();
+        assert_eq!((snapshot.cursor_row, snapshot.cursor_col), (1, 3));
+    }
+
+    #[test]
+    fn styles_split_rows_into_spans() {
+        let screen = render(b"a\x1b[1;31mbc\x1b[0md");
+        let row = &screen.snapshot().lines[0];
+        assert_eq!(row[0].text, "a");
+        assert_eq!(row[1].text, "bc");
+        assert!(row[1].style.bold);
+        assert_eq!(row[1].color, hex_color(&ColorSpec::PaletteIndex(1)));
+        assert!(row[2].text.starts_with('d'));
+        assert_eq!(row[2].color, None);
+    }
+
+    #[test]
+    fn restores_saved_cursor() {
+        let screen = render(b"ab\x1b7\r\n\x1b[2Ccd\x1b8e");
+        assert_eq!(screen.text(), "abe\n  cd\n\n\n");
+    }
+
+    #[test]
+    fn resizes_with_xtwinops() {
+        let screen = render(b"\x1b[8;10;40t");
+        let snapshot = screen.snapshot();
+        assert_eq!((snapshot.rows, snapshot.cols), (10, 40));
+    }
+
+    #[test]
+    fn huge_resizes_are_clamped() {
+        let screen = render(b"\x1b[8;100000;100000t");
+        let snapshot = screen.snapshot();
+        assert_eq!((snapshot.rows, snapshot.cols), (MAX_SIZE, MAX_SIZE));
+    }
+}
@@ -0,0 +1,282 @@
+//! A server-rendered snapshot of the session for browsers without JavaScript (or `curl`), served at `/static-view`.
+
+use std::fmt::Write;
+
+use escape_artist::{DiffSide, PromptMarkerKind, VteEventDto};
+
+const STYLE: &str = "
+body { background: #0f172a; color: #f9fafb; font-family: 'Consolas NF', monospace; padding: 0.5rem; word-break: break-all; }
+.text { white-space: pre-wrap; }
+.badge { display: inline-block; outline: 1px solid #94a3b8; border-radius: 2px; padding: 0 0.25rem; margin: 0.25rem; background: #1e293b; }
+.badge svg { vertical-align: middle; }
+.line-break { outline-color: #64748b; font-size: 0.75rem; }
+.inline-image { display: block; max-width: 100%; margin: 0.25rem; image-rendering: pixelated; }
+.ended { display: block; width: fit-content; }
+.pasted { text-decoration: underline dotted #38bdf8; }
+.linked { border-bottom: 1px solid #a78bfa; }
+a { color: inherit; text-decoration: none; }
+.prompt { border: 0; border-top: 1px solid #475569; margin: 0.5rem 0 0; }
+.removed { background: #7f1d1d; text-decoration: line-through; }
+.added { background: #14532d; }
+";
+
+/// Render the DTOs as a complete HTML page. Tooltips become `title` attributes, so hovering still works
+pub fn render(dtos: &[VteEventDto]) -> String {
+    let mut html = format!(
+        "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Escape Artist</title>\n<style>{STYLE}</style>\n</head>\n<body>\n"
+    );
+    for dto in dtos {
+        // writing to a String can't fail
+        let _ = render_dto(&mut html, dto);
+    }
+    html.push_str("\n</body>\n</html>\n");
+    html
+}
+
+fn render_dto(html: &mut String, dto: &VteEventDto) -> std::fmt::Result {
+    match dto {
+        VteEventDto::Print {
+            string,
+            color,
+            bg_color,
+            show_whitespace,
+            underline,
+            style: text_style,
+            pasted,
+            hyperlink,
+            ..
+        } => {
+            let string = if *show_whitespace {
+                string.replace(' ', "·")
+            } else {
+                string.clone()
+            };
+            let mut style = String::new();
+            if let Some(color) = color {
+                write!(style, "color: {color};")?;
+            }
+            if let Some(bg_color) = bg_color {
+                write!(style, "background-color: {bg_color};")?;
+            }
+            let lines = match (underline, text_style.strikethrough) {
+                (Some(_), true) => "underline line-through",
+                (Some(_), false) => "underline",
+                (None, true) => "line-through",
+                (None, false) => "",
+            };
+            if !lines.is_empty() {
+                // CSS calls these solid and wavy
+                let underline = match underline.as_deref() {
+                    Some("single") => "solid",
+                    Some("curly") => "wavy",
+                    Some(other) => other,
+                    None => "",
+                };
+                write!(
+                    style,
+                    "text-decoration: {};",
+                    format!("{lines} {underline}").trim_end()
+                )?;
+            }
+            if text_style.bold {
+                write!(style, "font-weight: bold;")?;
+            }
+            if text_style.dim {
+                write!(style, "opacity: 0.6;")?;
+            }
+            if text_style.italic {
+                write!(style, "font-style: italic;")?;
+            }
+            let (linked, title) = match hyperlink {
+                Some(uri) => (" linked", format!(" title=\"{}\"", escape(uri))),
+                None => ("", String::new()),
+            };
+            let href = hyperlink.as_deref().filter(|uri| is_safe_link(uri));
+            if let Some(href) = href {
+                write!(
+                    html,
+                    "<a href=\"{}\" target=\"_blank\" rel=\"noopener noreferrer\">",
+                    escape(href)
+                )?;
+            }
+            write!(
+                html,
+                "<span class=\"text{}{linked}\" style=\"{}\"{title}>{}</span>",
+                if *pasted { " pasted" } else { "" },
+                escape(&style),
+                escape(&string)
+            )?;
+            if href.is_some() {
+                write!(html, "</a>")?;
+            }
+            Ok(())
+        }
+        VteEventDto::GenericEscape {
+            title,
+            icon_svg,
+            tooltip,
+            raw_bytes,
+            ..
+        } => badge(html, title, icon_svg, tooltip, raw_bytes, None),
+        VteEventDto::ColorEscape {
+            title,
+            icon_svg,
+            tooltip,
+            color,
+            raw_bytes,
+            ..
+        } => badge(html, title, icon_svg, tooltip, raw_bytes, Some(color)),
+        VteEventDto::PromptMarker {
+            kind,
+            exit_code,
+            raw_bytes,
+        } => {
+            // each prompt starts a new section, so a command and its output stay together
+            if *kind == PromptMarkerKind::PromptStart {
+                write!(html, "<hr class=\"prompt\">")?;
+            }
+            let color = match exit_code {
+                Some(0) => "#22c55e".to_string(),
+                Some(_) => "#ef4444".to_string(),
+                None => "#38bdf8".to_string(),
+            };
+            badge(
+                html,
+                &Some(prompt_marker_label(*kind, *exit_code)),
+                &None,
+                &Some(dto.prompt_marker_tooltip()),
+                raw_bytes,
+                Some(&color),
+            )
+        }
+        VteEventDto::Sgr {
+            attributes,
+            raw_bytes,
+        } => badge(
+            html,
+            &Some("SGR".into()),
+            &None,
+            &Some(attributes.join("; ")),
+            raw_bytes,
+            None,
+        ),
+        VteEventDto::Image {
+            title,
+            tooltip,
+            data_url,
+            raw_bytes,
+            ..
+        } => {
+            badge(html, &Some(title.clone()), &None, &Some(tooltip.clone()), raw_bytes, None)?;
+            // we built the data URL ourselves, so it can't break out of the attribute
+            write!(
+                html,
+                "<img class=\"inline-image\" src=\"{data_url}\" alt=\"{}\">",
+                escape(tooltip)
+            )
+        }
+        VteEventDto::Diff { side, event } => {
+            let class = match side {
+                DiffSide::Removed => "removed",
+                DiffSide::Added => "added",
+            };
+            write!(html, "<span class=\"{class}\">")?;
+            render_dto(html, event)?;
+            write!(html, "</span>")
+        }
+        VteEventDto::InvisibleLineBreak {} => write!(html, "<br>"),
+        VteEventDto::LineBreak { title } => {
+            write!(
+                html,
+                "<span class=\"badge line-break\">{}</span>",
+                escape(title)
+            )
+        }
+        VteEventDto::SessionEnded {
+            exit_code,
+            sequence_count,
+        } => {
+            let (exit_code, outline) = match exit_code {
+                Some(0) => ("0".to_string(), "#22c55e"),
+                Some(code) => (code.to_string(), "#ef4444"),
+                None => ("unknown".to_string(), "#ef4444"),
+            };
+            write!(
+                html,
+                "<div class=\"badge ended\" style=\"outline-color: {outline}\">Session ended (exit code {exit_code}, {sequence_count} escape sequences)</div>"
+            )
+        }
+        VteEventDto::Truncated { limit } => write!(
+            html,
+            "<div class=\"badge ended\">Stopped decoding after {limit} escape sequences (--head)</div>"
+        ),
+        VteEventDto::EventsDropped { count } => write!(
+            html,
+            "<div class=\"badge ended\">{count} earlier events were dropped (--max-events)</div>"
+        ),
+        // the page is rendered from `all_dtos`, which never contains these
+        VteEventDto::Clear {} | VteEventDto::Annotation { .. } | VteEventDto::Skipped { .. } => {
+            Ok(())
+        }
+    }
+}
+
+fn prompt_marker_label(kind: PromptMarkerKind, exit_code: Option<i32>) -> String {
+    match (kind, exit_code) {
+        (PromptMarkerKind::PromptStart, _) => "Prompt".into(),
+        (PromptMarkerKind::CommandStart, _) => "Command".into(),
+        (PromptMarkerKind::OutputStart, _) => "Output".into(),
+        (PromptMarkerKind::CommandFinished, Some(exit_code)) => format!("Exit {exit_code}"),
+        (PromptMarkerKind::CommandFinished, None) => "Finished".into(),
+    }
+}
+
+fn badge(
+    html: &mut String,
+    title: &Option<String>,
+    icon_svg: &Option<String>,
+    tooltip: &Option<String>,
+    raw_bytes: &str,
+    color: Option<&String>,
+) -> std::fmt::Result {
+    let hover = match tooltip {
+        Some(tooltip) => format!("{tooltip}\n{raw_bytes}"),
+        None => raw_bytes.to_string(),
+    };
+    let style = color
+        .map(|color| format!(" style=\"outline-color: {}\"", escape(color)))
+        .unwrap_or_default();
+    write!(
+        html,
+        "<span class=\"badge\" title=\"{}\"{style}>",
+        escape(&hover)
+    )?;
+    // the SVGs are ours, not the child's, so they're safe to include as-is
+    if let Some(svg) = icon_svg {
+        html.push_str(svg);
+    }
+    match title {
+        Some(title) => write!(html, "{}", escape(title))?,
+        // without an icon or a title, the raw bytes are the only thing to show
+        None if icon_svg.is_none() => write!(html, "{}", escape(raw_bytes))?,
+        None => {}
+    }
+    write!(html, "</span>")
+}
+
+/// Whether a hyperlink from the child's output can be made clickable. Anything that could run script in
+/// the page, like a `javascript:` URI, only gets shown on hover
+fn is_safe_link(uri: &str) -> bool {
+    let scheme = uri.split_once(':').map_or("", |(scheme, _)| scheme);
+    ["http", "https", "ftp", "file", "mailto"]
+        .iter()
+        .any(|safe| scheme.eq_ignore_ascii_case(safe))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}